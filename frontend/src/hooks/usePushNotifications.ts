@@ -7,10 +7,6 @@ interface PushNotificationState {
   subscription: PushSubscription | null;
 }
 
-// VAPID public key - generated with web-push CLI
-const VAPID_PUBLIC_KEY =
-  "BJQSR9qm7FEPlfcSGoF_ooAszWGzQWa-BgXGKnnnx-SpQBgA1Ls-nsp1Ww-iCbxTjzNJdOw6t75ewRYmUCxt48w";
-
 export function usePushNotifications() {
   const [state, setState] = useState<PushNotificationState>({
     isSupported: false,
@@ -97,10 +93,11 @@ export function usePushNotifications() {
       // Get service worker registration
       const registration = await navigator.serviceWorker.ready;
 
-      // Subscribe to push notifications
+      // Subscribe to push notifications with the server's current VAPID key
+      const vapidPublicKey = await fetchVapidPublicKey();
       const subscription = await registration.pushManager.subscribe({
         userVisibleOnly: true,
-        applicationServerKey: urlBase64ToUint8Array(VAPID_PUBLIC_KEY),
+        applicationServerKey: urlBase64ToUint8Array(vapidPublicKey),
       });
 
       console.log("Push subscription created:", subscription);
@@ -168,11 +165,18 @@ function urlBase64ToUint8Array(base64String: string): Uint8Array {
   return outputArray;
 }
 
+async function fetchVapidPublicKey(): Promise<string> {
+  const response = await fetch("/api/push/vapid-public-key");
+  if (!response.ok) {
+    throw new Error("Failed to fetch VAPID public key");
+  }
+  const { publicKey } = (await response.json()) as { publicKey: string };
+  return publicKey;
+}
+
 async function sendSubscriptionToBackend(
   subscription: PushSubscription
 ): Promise<void> {
-  // TODO: Implement API endpoint to store subscription
-  // This should send the subscription to your backend
   const response = await fetch("/api/push/subscribe", {
     method: "POST",
     headers: {
@@ -189,7 +193,6 @@ async function sendSubscriptionToBackend(
 async function removeSubscriptionFromBackend(
   subscription: PushSubscription
 ): Promise<void> {
-  // TODO: Implement API endpoint to remove subscription
   await fetch("/api/push/unsubscribe", {
     method: "POST",
     headers: {
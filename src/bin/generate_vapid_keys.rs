@@ -0,0 +1,22 @@
+use sse_delta_snapshot::vapid::{VapidKeys, DEFAULT_SUBJECT};
+use std::path::Path;
+
+/// Generate a VAPID key pair for Web Push.
+///
+/// Prints both keys as environment variables. With a path argument, the private key is also
+/// written to that file (for `VAPID_PRIVATE_KEY_FILE`), readable only by the owner. An existing
+/// file is left alone.
+fn main() {
+    let keys = VapidKeys::generate(DEFAULT_SUBJECT);
+
+    if let Some(path) = std::env::args().nth(1) {
+        if let Err(e) = keys.write_private_key(Path::new(&path)) {
+            eprintln!("Failed to write private key: {}", e);
+            std::process::exit(1);
+        }
+        eprintln!("✓ Wrote private key to {}", path);
+    }
+
+    println!("VAPID_PRIVATE_KEY={}", keys.private_key());
+    println!("VAPID_PUBLIC_KEY={}", keys.public_key());
+}
@@ -0,0 +1,78 @@
+use sse_delta_snapshot::storage::Storage;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: storage_admin <command>
+
+Commands:
+  rebuild-projection   Replay the event log and regenerate the resources table
+  reindex              Drop the search index and rebuild it from the database
+  check-index          Report resources and events missing from the search index or the database
+
+Uses DATA_DIR (default: ./data). Stop the server first: the database can only be
+opened by one process at a time.";
+
+#[tokio::main]
+async fn main() {
+    let command = std::env::args().nth(1);
+
+    let data_dir = std::env::var("DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("./data"));
+
+    match command.as_deref() {
+        Some("rebuild-projection") => {
+            let storage = open_storage(&data_dir).await;
+            match storage.rebuild_projection().await {
+                Ok(report) => {
+                    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                    if !report.failures.is_empty() {
+                        std::process::exit(2);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to rebuild projection: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("reindex") => {
+            let storage = open_storage(&data_dir).await;
+            match storage.reindex().await {
+                Ok(report) => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+                Err(e) => {
+                    eprintln!("Failed to reindex: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("check-index") => {
+            let storage = open_storage(&data_dir).await;
+            match storage.check_index().await {
+                Ok(report) => {
+                    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                    if !report.is_consistent() {
+                        std::process::exit(2);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to check index: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn open_storage(data_dir: &Path) -> Storage {
+    match Storage::new(data_dir).await {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Failed to open storage at {}: {}", data_dir.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Error types shared by the storage layer and the HTTP handlers.
+//!
+//! [`StorageError`] is what every [`crate::storage::Storage`] method returns, so library
+//! callers can match on the kind of failure. Handlers turn it into an RFC 7807
+//! `application/problem+json` response through [`Problem`].
+
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::validation::{Finding, Violation};
+
+/// Failure of a storage operation.
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    /// The requested event or resource does not exist
+    #[error("not found: {0}")]
+    NotFound(String),
+    /// The operation conflicts with the current state (e.g. the database is opened elsewhere)
+    #[error("conflict: {0}")]
+    Conflict(String),
+    /// The event cannot be applied, e.g. a JSONCommit with a malformed commit. `field` is the
+    /// path of the offending attribute (e.g. `data.resource_id`) when it is known.
+    #[error("invalid event {event_id}: {message}")]
+    InvalidEvent {
+        event_id: String,
+        field: Option<String>,
+        message: String,
+    },
+    /// The resource resulting from a JSONCommit does not match the schema the commit names
+    #[error("event {event_id} violates schema {schema}: {}", describe(violations))]
+    SchemaViolations {
+        event_id: String,
+        schema: String,
+        violations: Vec<Violation>,
+    },
+    /// The search query cannot be parsed or uses unsupported options
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+    /// A stored record could not be decoded
+    #[error("corrupt record {key}: {message}")]
+    CorruptRecord { key: String, message: String },
+    /// A value could not be encoded for storage
+    #[error("serialization failed: {0}")]
+    Serialization(String),
+    /// The search index failed or its background indexer stopped
+    #[error("search index error: {0}")]
+    Index(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Any other redb failure (transactions, tables, disk storage)
+    #[error("database error: {0}")]
+    Database(#[source] Box<redb::Error>),
+}
+
+/// `path: message` of every violation, for log lines and problem details.
+fn describe(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(|v| format!("{}: {}", v.path, v.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl StorageError {
+    /// HTTP status that describes this failure to API clients.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            StorageError::NotFound(_) => StatusCode::NOT_FOUND,
+            StorageError::Conflict(_) => StatusCode::CONFLICT,
+            StorageError::InvalidEvent { .. } | StorageError::SchemaViolations { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            StorageError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            StorageError::CorruptRecord { .. }
+            | StorageError::Serialization(_)
+            | StorageError::Index(_)
+            | StorageError::Io(_)
+            | StorageError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Short, stable name of the error kind, used in problem type URIs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StorageError::NotFound(_) => "not-found",
+            StorageError::Conflict(_) => "conflict",
+            StorageError::InvalidEvent { .. } => "invalid-event",
+            StorageError::SchemaViolations { .. } => "schema-violation",
+            StorageError::InvalidQuery(_) => "invalid-query",
+            StorageError::CorruptRecord { .. } => "corrupt-record",
+            StorageError::Serialization(_) => "serialization",
+            StorageError::Index(_) => "search-index",
+            StorageError::Io(_) => "io",
+            StorageError::Database(_) => "database",
+        }
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Serialization(e.to_string())
+    }
+}
+
+impl From<bincode::Error> for StorageError {
+    fn from(e: bincode::Error) -> Self {
+        StorageError::Serialization(e.to_string())
+    }
+}
+
+impl From<tantivy::TantivyError> for StorageError {
+    fn from(e: tantivy::TantivyError) -> Self {
+        StorageError::Index(e.to_string())
+    }
+}
+
+impl From<tantivy::query::QueryParserError> for StorageError {
+    fn from(e: tantivy::query::QueryParserError) -> Self {
+        StorageError::InvalidQuery(e.to_string())
+    }
+}
+
+impl From<redb::DatabaseError> for StorageError {
+    fn from(e: redb::DatabaseError) -> Self {
+        match e {
+            redb::DatabaseError::DatabaseAlreadyOpen => {
+                StorageError::Conflict("database is already open in another process".to_string())
+            }
+            e => StorageError::Database(Box::new(e.into())),
+        }
+    }
+}
+
+macro_rules! from_redb_error {
+    ($($error:ty),*) => {
+        $(
+            impl From<$error> for StorageError {
+                fn from(e: $error) -> Self {
+                    StorageError::Database(Box::new(e.into()))
+                }
+            }
+        )*
+    };
+}
+
+from_redb_error!(
+    redb::TransactionError,
+    redb::TableError,
+    redb::StorageError,
+    redb::CommitError
+);
+
+/// RFC 7807 problem details, served as `application/problem+json`.
+///
+/// Besides the standard members, problems about a request name the offending `field`
+/// (a body path such as `data.resource_id`, or a query parameter) and the `event_id` of the
+/// rejected CloudEvent, so producers can debug rejections without server logs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Problem {
+    /// URI reference identifying the problem type
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    /// Every schema violation, for commits whose resource does not match its schema
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+    /// Every CloudEvents error, for events that do not conform to the specification
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+}
+
+impl Problem {
+    /// A problem without a specific type: `about:blank` titled after the status.
+    pub fn new(status: StatusCode, detail: impl Into<Option<String>>) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            field: None,
+            event_id: None,
+            violations: Vec::new(),
+            findings: Vec::new(),
+        }
+    }
+
+    /// A problem of a specific type, identified as `/problems/{kind}`.
+    pub fn typed(status: StatusCode, kind: &str, detail: impl Into<String>) -> Self {
+        Self {
+            problem_type: format!("/problems/{}", kind),
+            ..Problem::new(status, detail.into())
+        }
+    }
+
+    /// 400 for a query or path parameter that is missing or has an unusable value.
+    pub fn invalid_parameter(field: &str, detail: impl Into<String>) -> Self {
+        Problem::typed(StatusCode::BAD_REQUEST, "invalid-parameter", detail).with_field(field)
+    }
+
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+
+    pub fn with_event_id(mut self, event_id: impl Into<String>) -> Self {
+        self.event_id = Some(event_id.into());
+        self
+    }
+}
+
+/// Name of the offending field in a deserialization error: the path where it failed, or for
+/// a missing field at that path, the missing field itself.
+pub fn error_field(path: &str, message: &str) -> Option<String> {
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next());
+    let path = Some(path).filter(|p| !p.is_empty() && *p != ".");
+    match (path, missing) {
+        (Some(path), Some(name)) => Some(format!("{}.{}", path, name)),
+        (Some(path), None) => Some(path.to_string()),
+        (None, name) => name.map(str::to_string),
+    }
+}
+
+impl From<StatusCode> for Problem {
+    fn from(status: StatusCode) -> Self {
+        Problem::new(status, None)
+    }
+}
+
+impl From<StorageError> for Problem {
+    fn from(e: StorageError) -> Self {
+        let status = e.status();
+        // Internal failures are logged server-side; clients only learn what kind they were
+        let detail = if status.is_server_error() {
+            eprintln!("[handlers] storage error: {}", e);
+            format!("internal {} error", e.kind())
+        } else {
+            e.to_string()
+        };
+        let problem = Problem::typed(status, e.kind(), detail);
+        match e {
+            StorageError::InvalidEvent {
+                event_id, field, ..
+            } => Problem {
+                field,
+                ..problem.with_event_id(event_id)
+            },
+            StorageError::SchemaViolations {
+                event_id,
+                violations,
+                ..
+            } => Problem {
+                violations,
+                ..problem.with_event_id(event_id)
+            },
+            _ => problem,
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_storage_error_becomes_problem_json() {
+        let error = StorageError::InvalidEvent {
+            event_id: "event-1".to_string(),
+            field: Some("data.resource_id".to_string()),
+            message: "missing field `resource_id`".to_string(),
+        };
+        let response = Problem::from(error).into_response();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: Problem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.problem_type, "/problems/invalid-event");
+        assert_eq!(problem.title, "Unprocessable Entity");
+        assert!(problem.detail.unwrap().contains("event-1"));
+        assert_eq!(problem.event_id.as_deref(), Some("event-1"));
+        assert_eq!(problem.field.as_deref(), Some("data.resource_id"));
+    }
+
+    #[test]
+    fn test_error_field() {
+        assert_eq!(
+            error_field(".", "missing field `id`"),
+            Some("id".to_string())
+        );
+        assert_eq!(
+            error_field("data", "missing field `resource_id`"),
+            Some("data.resource_id".to_string())
+        );
+        assert_eq!(
+            error_field("time", "invalid type: integer `1`, expected a string"),
+            Some("time".to_string())
+        );
+        assert_eq!(error_field(".", "expected value"), None);
+    }
+
+    #[test]
+    fn test_internal_errors_hide_details() {
+        let error = StorageError::CorruptRecord {
+            key: "00000000000000000001".to_string(),
+            message: "io error".to_string(),
+        };
+        let problem = Problem::from(error);
+        assert_eq!(problem.status, 500);
+        assert_eq!(
+            problem.detail.as_deref(),
+            Some("internal corrupt-record error")
+        );
+    }
+}
@@ -1,44 +1,211 @@
 //! HTTP handlers for /events, /resources, and /query endpoints
 
 use axum::{
-    extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Path, Query, Request, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
     response::sse::{Event, KeepAlive, Sse},
     response::{IntoResponse, Response},
     Json,
 };
-use futures_util::stream;
-use serde::{Deserialize, Serialize};
+use futures_util::stream::Stream;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use std::sync::Arc;
-use tokio_stream::wrappers::BroadcastStream;
+use tokio::sync::{broadcast, watch};
 use tokio_stream::StreamExt;
 
-use crate::schemas::{CloudEvent, JSONCommit};
-use crate::storage::{SearchResult, Storage};
-use crate::types::PushSubscription;
+use crate::error::{error_field, Problem, StorageError};
+use crate::push::PushDispatcher;
+use crate::schemas::CloudEvent;
+use crate::storage::{
+    normalize_sequence, sequence_key, AsOf, CommittedState, IndexCheckReport, RebuildReport,
+    ReindexReport, SearchQuery, SearchResult, SearchSort, Storage, StoreOutcome, Suggestion,
+    DEFAULT_FUZZINESS, MAX_FUZZINESS,
+};
+use crate::validation::{self, Finding, Severity};
 
 /// Shared application state with storage (handlers view)
 ///
-/// This AppState includes the push subscription store so push-related handlers
-/// (subscribe/unsubscribe) can access and mutate subscriptions when needed.
+/// Push subscriptions live in storage alongside the events, so they survive restarts.
 #[derive(Clone)]
 pub struct AppState {
     pub storage: Arc<Storage>,
     pub tx: tokio::sync::broadcast::Sender<CloudEvent>,
-    /// Push notification subscriptions (shared across handlers)
-    pub push_subscriptions: Arc<tokio::sync::RwLock<Vec<PushSubscription>>>,
+    /// Sends push notifications for committed events in the background
+    pub push: PushDispatcher,
+    /// Set to `true` when the server starts shutting down; open SSE streams then end
+    pub shutdown: watch::Sender<bool>,
 }
 
-/// Convenience constructor for handlers to create an AppState when needed.
+/// State for tests. Push notifications are signed with a freshly generated VAPID key pair.
+#[cfg(test)]
 impl AppState {
     pub fn new(storage: Arc<Storage>, tx: tokio::sync::broadcast::Sender<CloudEvent>) -> Self {
+        use crate::vapid::{self, VapidKeys};
+
         Self {
+            push: PushDispatcher::spawn(&storage, VapidKeys::generate(vapid::DEFAULT_SUBJECT)),
             storage,
             tx,
-            push_subscriptions: Arc::new(tokio::sync::RwLock::new(Vec::new())),
+            shutdown: watch::channel(false).0,
+        }
+    }
+}
+
+/// JSON request body (`application/json` or `application/*+json`) whose rejections are
+/// reported as problem+json naming the offending field and, for CloudEvent bodies, the
+/// event id.
+pub struct ApiJson<T>(pub T);
+
+impl<S, T> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json_content_type(req.headers()) {
+            return Err(Problem::typed(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported-media-type",
+                "expected a JSON body (Content-Type: application/json)",
+            ));
+        }
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| Problem::typed(e.status(), "unreadable-body", e.body_text()))?;
+
+        let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+        let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+            let mut problem = match e.inner().classify() {
+                serde_json::error::Category::Data => invalid_body(&e),
+                _ => Problem::typed(StatusCode::BAD_REQUEST, "malformed-json", e.to_string()),
+            };
+            problem.event_id = serde_json::from_slice::<Value>(&bytes)
+                .ok()
+                .and_then(|body| body.get("id")?.as_str().map(str::to_string));
+            problem
+        })?;
+        deserializer.end().map_err(|e| {
+            Problem::typed(StatusCode::BAD_REQUEST, "malformed-json", e.to_string())
+        })?;
+
+        Ok(ApiJson(value))
+    }
+}
+
+/// 422 for JSON that does not have the expected shape, naming the offending field.
+fn invalid_body(e: &serde_path_to_error::Error<serde_json::Error>) -> Problem {
+    let message = e.inner().to_string();
+    let field = error_field(&e.path().to_string(), &message);
+    Problem {
+        field,
+        ..Problem::typed(StatusCode::UNPROCESSABLE_ENTITY, "invalid-body", message)
+    }
+}
+
+/// CloudEvent request body that conforms to CloudEvents 1.0 (see
+/// [`validation::validate_cloudevent`]). Nonconforming events are rejected with a 422 listing
+/// every error; NL-GOV profile recommendations do not reject an event.
+pub struct ValidCloudEvent(pub CloudEvent);
+
+impl ValidCloudEvent {
+    /// Validate a CloudEvent in its JSON event format.
+    #[allow(clippy::result_large_err)] // the problem becomes the response body
+    pub fn from_value(body: Value) -> Result<Self, Problem> {
+        let event_id = body.get("id").and_then(Value::as_str).map(str::to_string);
+        let errors: Vec<Finding> = validation::validate_cloudevent(&body)
+            .into_iter()
+            .filter(|f| f.severity == Severity::Error)
+            .collect();
+        if let Some(first) = errors.first() {
+            let detail = errors
+                .iter()
+                .map(|f| f.message.as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(Problem {
+                field: Some(first.attribute.clone()).filter(|a| !a.is_empty()),
+                event_id,
+                findings: errors,
+                ..Problem::typed(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "invalid-cloudevent",
+                    format!("not a valid CloudEvents 1.0 event: {}", detail),
+                )
+            });
         }
+
+        serde_path_to_error::deserialize(body)
+            .map(ValidCloudEvent)
+            .map_err(|e| Problem {
+                event_id,
+                ..invalid_body(&e)
+            })
+    }
+}
+
+impl<S> FromRequest<S> for ValidCloudEvent
+where
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let ApiJson(body) = ApiJson::<Value>::from_request(req, state).await?;
+        ValidCloudEvent::from_value(body)
+    }
+}
+
+/// Whether the request declares a JSON body, as `axum::Json` checks it.
+fn is_json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    match essence.strip_prefix("application/") {
+        Some(subtype) => subtype == "json" || subtype.ends_with("+json"),
+        None => false,
+    }
+}
+
+/// Query string parameters whose rejections are reported as problem+json naming the
+/// offending parameter.
+pub struct ApiQuery<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::try_from_uri(&parts.uri).map_err(|e| {
+            // axum reports "Failed to deserialize query string: <param>: <message>"
+            let text = e.body_text();
+            let reason = text.split_once(": ").map_or(text.as_str(), |(_, r)| r);
+            let (param, message) = match reason.split_once(": ") {
+                Some((param, message)) if !param.contains(' ') => (param, message),
+                _ => ("", reason),
+            };
+            let mut problem = Problem::typed(StatusCode::BAD_REQUEST, "invalid-parameter", message);
+            problem.field = error_field(param, message);
+            problem
+        })?;
+        Ok(ApiQuery(value))
     }
 }
 
@@ -67,12 +234,133 @@ fn default_limit() -> usize {
     10000
 }
 
+/// Largest page of search hits; also the default page size.
+const MAX_QUERY_LIMIT: usize = 10000;
+
+/// Deepest search page that can be requested, as `offset`.
+const MAX_QUERY_OFFSET: usize = 100_000;
+
+/// Largest number of title suggestions per request.
+const MAX_SUGGEST_LIMIT: usize = 50;
+
+/// Reject a `limit` outside `1..=max`.
+#[allow(clippy::result_large_err)] // the problem becomes the response body
+fn check_limit(limit: usize, max: usize) -> Result<(), Problem> {
+    if limit == 0 || limit > max {
+        return Err(Problem::invalid_parameter(
+            "limit",
+            format!("must be between 1 and {}, got {}", max, limit),
+        ));
+    }
+    Ok(())
+}
+
 /// Query parameters for search
 #[derive(Debug, Deserialize)]
 pub struct QueryParams {
+    #[serde(default)]
     pub q: String,
     #[serde(default = "default_limit")]
     pub limit: usize,
+    /// Number of hits to skip; pass the previous response's `next_offset` to page
+    #[serde(default = "default_offset")]
+    pub offset: usize,
+    /// Maximum edit distance for fuzzy matching (0-2, default 1; 0 disables fuzzy matching)
+    pub fuzziness: Option<u8>,
+    /// Restrict results to a resource type (e.g. `issue`) or CloudEvent type
+    #[serde(rename = "type")]
+    pub doc_type: Option<String>,
+    pub status: Option<String>,
+    /// Only results timestamped at or after this RFC 3339 time or date (YYYY-MM-DD)
+    pub from: Option<String>,
+    /// Only results timestamped at or before this RFC 3339 time or date (whole day included)
+    pub to: Option<String>,
+    /// `relevance` (default) or `recent` for newest first
+    pub sort: Option<String>,
+    /// Read-your-writes: commit pending index changes before searching, so events stored
+    /// just before the query are found
+    #[serde(default)]
+    pub consistent: bool,
+}
+
+impl QueryParams {
+    /// Validate the parameters and turn them into a storage query.
+    #[allow(clippy::result_large_err)] // the problem becomes the response body
+    fn into_search_query(self) -> Result<SearchQuery, Problem> {
+        check_limit(self.limit, MAX_QUERY_LIMIT)?;
+        if self.offset > MAX_QUERY_OFFSET {
+            return Err(Problem::invalid_parameter(
+                "offset",
+                format!("must be at most {}, got {}", MAX_QUERY_OFFSET, self.offset),
+            ));
+        }
+        let fuzziness = self.fuzziness.unwrap_or(DEFAULT_FUZZINESS);
+        if fuzziness > MAX_FUZZINESS {
+            return Err(Problem::invalid_parameter(
+                "fuzziness",
+                format!("must be at most {}, got {}", MAX_FUZZINESS, fuzziness),
+            ));
+        }
+        let sort = match self.sort.as_deref() {
+            None | Some("relevance") => SearchSort::Relevance,
+            Some("recent") => SearchSort::Recent,
+            Some(other) => {
+                return Err(Problem::invalid_parameter(
+                    "sort",
+                    format!("expected `relevance` or `recent`, got `{}`", other),
+                ))
+            }
+        };
+
+        Ok(SearchQuery {
+            q: self.q,
+            limit: self.limit,
+            offset: self.offset,
+            doc_type: self.doc_type,
+            status: self.status,
+            fuzziness,
+            from: self
+                .from
+                .as_deref()
+                .map(|t| parse_time("from", t, false))
+                .transpose()?,
+            to: self
+                .to
+                .as_deref()
+                .map(|t| parse_time("to", t, true))
+                .transpose()?,
+            sort,
+        })
+    }
+}
+
+/// Parse the `param` value as an RFC 3339 time or a plain date. A date means its first second,
+/// or its last second when `end_of_day` is set, so date ranges include both boundary days.
+#[allow(clippy::result_large_err)] // the problem becomes the response body
+fn parse_time(
+    param: &str,
+    value: &str,
+    end_of_day: bool,
+) -> Result<chrono::DateTime<chrono::Utc>, Problem> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    let invalid = || {
+        Problem::invalid_parameter(
+            param,
+            format!(
+                "expected an RFC 3339 time or YYYY-MM-DD date, got `{}`",
+                value
+            ),
+        )
+    };
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| invalid())?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    time.map(|t| t.and_utc()).ok_or_else(invalid)
 }
 
 /// Query parameters for listing events (used for JSON listing or snapshot pagination)
@@ -95,13 +383,22 @@ pub struct EventsListParams {
     pub after_seq: Option<String>,
 }
 
+/// Number of events read per page when catching a reconnecting client up from storage.
+const CATCH_UP_PAGE_SIZE: usize = 1000;
+
+/// Reconnect delay suggested to SSE clients when the server shuts down.
+const SHUTDOWN_RETRY: std::time::Duration = std::time::Duration::from_secs(5);
+
 /// GET /events - Returns an SSE stream by default. If the query `?format=json` is present,
 /// the handler will return a JSON list instead (keeps frontend compatibility: SSE is default).
+///
+/// Reconnecting EventSource clients send a `Last-Event-ID` header; those clients skip the
+/// snapshot and only receive the deltas stored after that sequence, followed by live deltas.
 pub async fn get_or_stream_events(
     State(state): State<AppState>,
-    _headers: HeaderMap,
-    Query(params): Query<EventsListParams>,
-) -> Result<Response, StatusCode> {
+    headers: HeaderMap,
+    ApiQuery(params): ApiQuery<EventsListParams>,
+) -> Result<Response, Problem> {
     // Only return JSON when explicitly requested via query param `?format=json`.
     let want_json = params
         .format
@@ -114,14 +411,10 @@ pub async fn get_or_stream_events(
         let events = state
             .storage
             .list_events_after(params.after_seq.clone(), params.limit)
-            .await
-            .map_err(|e| {
-                eprintln!("Failed to list events: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+            .await?;
 
         // Filter events by topic if provided
-        let mut filtered: Vec<CloudEvent> = if let Some(topic) = params.topic.as_deref() {
+        let filtered: Vec<CloudEvent> = if let Some(topic) = params.topic.as_deref() {
             events
                 .into_iter()
                 .filter(|e| {
@@ -141,307 +434,306 @@ pub async fn get_or_stream_events(
         return Ok(Json(filtered).into_response());
     }
 
-    // Default: return SSE stream (snapshot followed by deltas)
-    let rx = state.tx.subscribe();
-
-    // Use storage to build a snapshot (paginated)
-    let snapshot_events = state
-        .storage
-        .list_events_after(params.after_seq.clone(), params.limit)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to build snapshot events: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    // Default: return SSE stream (snapshot followed by deltas, or only deltas when resuming)
+    let sse = event_stream(&state, &headers, params.after_seq.clone(), params.limit).await?;
 
-    // Keep snapshot order as provided by storage (earliest-first). No reversal applied here.
-
-    let snapshot = serde_json::to_string(&snapshot_events).unwrap_or_else(|_| "[]".to_string());
-
-    let stream = stream::once(async move {
-        Ok::<Event, Infallible>(Event::default().event("snapshot").data(snapshot))
-    })
-    .chain(
-        BroadcastStream::new(rx)
-            .map(|msg| {
-                let delta = msg.unwrap();
-                let json = serde_json::to_string(&delta).unwrap_or_else(|_| "{}".to_string());
-                Event::default().event("delta").data(json)
-            })
-            .map(Ok),
-    );
-
-    let sse = Sse::new(stream).keep_alive(KeepAlive::default());
     Ok(sse.into_response())
 }
 
-/// Response for query endpoint
-#[derive(Debug, Serialize)]
-pub struct QueryResponse {
-    pub query: String,
-    pub results: Vec<SearchResult>,
-    pub count: usize,
-}
-
-/// Error response type
-#[derive(Debug, Serialize)]
-#[allow(dead_code)]
-pub struct ErrorResponse {
-    pub error: String,
-}
-
-/// POST /events - Handle incoming CloudEvents (Command + Sync)
-/// This is where resources are created, updated, and deleted
-pub async fn handle_event(
-    State(state): State<AppState>,
-    Json(mut event): Json<CloudEvent>,
-) -> Result<Response, StatusCode> {
-    // Store the event and get the assigned server sequence key
-    let seq_key = state.storage.store_event(&event).await.map_err(|e| {
-        eprintln!("Failed to store event: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    // Attach the assigned sequence to the CloudEvent so clients can use it for ordering/pagination
-    event.sequence = Some(seq_key.clone());
-
-    // Process the event to update resources
-    if let Err(e) = process_event(&state, &event).await {
-        eprintln!("Failed to process event: {}", e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-
-    // Broadcast the event (with attached sequence) to SSE subscribers
-    let _ = state.tx.send(event.clone());
-
-    Ok((StatusCode::ACCEPTED, Json(event)).into_response())
+/// Read the `Last-Event-ID` header sent by reconnecting EventSource clients and
+/// normalize it to a sequence key. Invalid values are ignored.
+pub fn last_event_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(normalize_sequence)
 }
 
-/// Process an event and update resources accordingly
-pub async fn process_event(
+/// Build the SSE stream shared by `GET /events` and `GET /events/stream`.
+///
+/// Without a `Last-Event-ID` header the client first receives a `snapshot` event with up to
+/// `limit` events after `after_seq`. With the header the snapshot is skipped and the client is
+/// caught up from storage instead. Every emitted event carries its sequence as SSE `id:` so
+/// the browser can resume from it after a disconnect. When the server shuts down the stream
+/// ends with a `shutdown` event whose `retry:` hint tells the browser when to reconnect.
+pub async fn event_stream(
     state: &AppState,
-    event: &CloudEvent,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Extract data from the event
-    let data = match &event.data {
-        Some(d) => d,
-        None => return Ok(()), // No data to process
-    };
-
-    // Check if this is a JSONCommit event (accept both legacy and NL-VNG names)
-    if event.event_type == "nl.vng.zaken.json-commit.v1" || event.event_type == "json.commit" {
-        // Log the incoming event and data shape for diagnostics
-        println!(
-            "[handlers] processing json-commit event id={} subject={:?} source={}",
-            event.id, event.subject, event.source
-        );
-
-        let commit: JSONCommit = serde_json::from_value(data.clone())?;
-
-        // Log commit details that matter for resource creation
-        println!(
-            "[handlers] commit parsed: resource_id={} schema={} resource_data_present={} patch_present={}",
-            commit.resource_id,
-            commit.schema,
-            commit.resource_data.is_some(),
-            commit.patch.is_some()
-        );
+    headers: &HeaderMap,
+    after_seq: Option<String>,
+    limit: usize,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StorageError> {
+    // Subscribe before reading from storage so nothing committed in between is missed.
+    let rx = state.tx.subscribe();
 
-        // Handle deletion
-        if commit.deleted.unwrap_or(false) {
-            println!("[handlers] deleting resource id={}", commit.resource_id);
-            state.storage.delete_resource(&commit.resource_id).await?;
-            return Ok(());
+    let (snapshot, resume_from) = match last_event_id(headers) {
+        Some(last_seen) => (None, Some(last_seen)),
+        None => {
+            let snapshot_events = state
+                .storage
+                .list_events_after(after_seq.clone(), limit)
+                .await?;
+            let last_seq = snapshot_events.last().and_then(|e| e.sequence.clone());
+            let json = serde_json::to_string(&snapshot_events).unwrap_or_else(|_| "[]".to_string());
+            let mut event = Event::default().event("snapshot").data(json);
+            if let Some(seq) = &last_seq {
+                event = event.id(seq.clone());
+            }
+            let resume_from =
+                last_seq.or_else(|| after_seq.as_deref().and_then(normalize_sequence));
+            (Some(event), resume_from)
         }
+    };
 
-        // Determine resource type more robustly:
-        // 1. Prefer schema hint (if it contains known type names)
-        // 2. Fallback to subject hint
-        // 3. Inspect resource_data keys (title/content/cta/moments/url)
-        // 4. Otherwise "unknown"
-        let mut resource_type = extract_resource_type_from_schema(&commit.schema).to_string();
-
-        if resource_type == "unknown" {
-            if let Some(subject) = &event.subject {
-                let subj_type = extract_resource_type_from_subject(subject);
-                if subj_type != "unknown" {
-                    resource_type = subj_type.to_string();
+    let deltas = delta_stream(state.storage.clone(), rx, resume_from).map(|message| {
+        let event = match message {
+            DeltaMessage::Delta(delta) => {
+                let json = serde_json::to_string(&delta).unwrap_or_else(|_| "{}".to_string());
+                let mut event = Event::default().event("delta").data(json);
+                if let Some(seq) = &delta.sequence {
+                    event = event.id(seq.clone());
                 }
+                event
             }
+            DeltaMessage::Resync { after_seq } => Event::default()
+                .event("resync")
+                .data(serde_json::json!({ "after_seq": after_seq }).to_string()),
+        };
+        Ok(event)
+    });
+
+    let mut shutdown = state.shutdown.subscribe();
+    let stream = async_stream::stream! {
+        if let Some(snapshot) = snapshot {
+            yield Ok(snapshot);
         }
 
-        if resource_type == "unknown" {
-            if let Some(resource_data) = &commit.resource_data {
-                if resource_data.is_object() {
-                    let obj = resource_data.as_object().unwrap();
-                    if obj.contains_key("title") {
-                        resource_type = "issue".to_string();
-                    } else if obj.contains_key("content") {
-                        resource_type = "comment".to_string();
-                    } else if obj.contains_key("cta") {
-                        resource_type = "task".to_string();
-                    } else if obj.contains_key("moments") {
-                        resource_type = "planning".to_string();
-                    } else if obj.get("url").is_some() || obj.get("size").is_some() {
-                        resource_type = "document".to_string();
-                    }
+        let mut deltas = std::pin::pin!(deltas);
+        loop {
+            // `None` once the server is shutting down (or the app state is gone)
+            let next = tokio::select! {
+                item = deltas.next() => Some(item),
+                _ = shutdown.wait_for(|down| *down) => None,
+            };
+            match next {
+                Some(Some(event)) => yield event,
+                Some(None) => return,
+                None => {
+                    yield Ok(Event::default()
+                        .event("shutdown")
+                        .retry(SHUTDOWN_RETRY)
+                        .data(r#"{"reason":"server shutting down"}"#));
+                    return;
                 }
             }
         }
+    };
 
-        // Final diagnostics before storing
-        println!(
-            "[handlers] storing resource id={} determined_type={} resource_data_present={}",
-            commit.resource_id,
-            resource_type,
-            commit.resource_data.is_some()
-        );
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
 
-        // Get existing resource if it exists
-        let existing_resource = state.storage.get_resource(&commit.resource_id).await?;
-
-        // Apply changes (merge patch or replace with resource_data)
-        let new_resource = if let Some(mut existing) = existing_resource {
-            // Apply patch if provided
-            if let Some(patch) = &commit.patch {
-                println!(
-                    "[handlers] applying patch to existing resource id={}",
-                    commit.resource_id
-                );
-                apply_json_merge_patch(&mut existing, patch);
-            }
-            // Override with full resource_data if provided
-            if let Some(resource_data) = &commit.resource_data {
-                println!(
-                    "[handlers] replacing existing resource id={} with provided resource_data",
-                    commit.resource_id
-                );
-                existing = resource_data.clone();
+/// Item produced by [`delta_stream`].
+#[derive(Debug, Clone)]
+pub enum DeltaMessage {
+    /// An event to forward to the client.
+    Delta(Box<CloudEvent>),
+    /// Missed events could not be re-read from storage; the client must refetch its state.
+    /// `after_seq` is the last sequence the client received, if any.
+    Resync { after_seq: Option<String> },
+}
+
+/// Stream of deltas following `after_seq`.
+///
+/// Events stored after `after_seq` are replayed from storage first, then live events are
+/// taken from `rx`. Because `rx` must be subscribed before the catch-up read, events that
+/// were both read from storage and broadcast are skipped so no delta is sent twice.
+///
+/// Concurrent requests may broadcast their events out of sequence order. A live event that
+/// skips a sequence is not sent by itself: the missing range is re-read from storage, where
+/// every broadcast event has been stored, and the late broadcasts are skipped.
+///
+/// When the subscriber falls behind the broadcast buffer (`Lagged`), the missed range is
+/// re-read from storage starting at the last sequence it received. If that read fails a
+/// single [`DeltaMessage::Resync`] is emitted and the stream ends.
+pub fn delta_stream(
+    storage: Arc<Storage>,
+    mut rx: broadcast::Receiver<CloudEvent>,
+    after_seq: Option<String>,
+) -> impl Stream<Item = DeltaMessage> {
+    async_stream::stream! {
+        // Highest sequence sent to the client; catch-up reads resume from here and older
+        // live events are duplicates.
+        let mut last_sent = after_seq;
+        let mut catching_up = last_sent.is_some();
+
+        loop {
+            if catching_up {
+                let page = storage
+                    .list_events_after(last_sent.clone(), CATCH_UP_PAGE_SIZE)
+                    .await
+                    .map_err(|e| {
+                        eprintln!(
+                            "[handlers] failed to catch up after seq={:?}: {}",
+                            last_sent, e
+                        )
+                    });
+                let Ok(page) = page else {
+                    yield DeltaMessage::Resync { after_seq: last_sent };
+                    return;
+                };
+                catching_up = page.len() >= CATCH_UP_PAGE_SIZE;
+                for event in page {
+                    if event.sequence.is_some() {
+                        last_sent = event.sequence.clone();
+                    }
+                    yield DeltaMessage::Delta(Box::new(event));
+                }
+                continue;
             }
-            existing
-        } else {
-            // New resource - use resource_data if available, else empty object
-            commit
-                .resource_data
-                .clone()
-                .unwrap_or_else(|| serde_json::json!({}))
-        };
 
-        // Store the updated resource
-        if let Err(e) = state
-            .storage
-            .store_resource(&commit.resource_id, &resource_type, &new_resource)
-            .await
-        {
-            eprintln!(
-                "[handlers] failed to store resource id={} type={} error={}",
-                commit.resource_id, resource_type, e
-            );
-            return Err(e);
-        } else {
-            println!(
-                "[handlers] successfully stored resource id={} type={}",
-                commit.resource_id, resource_type
-            );
-        }
-    } else {
-        // For other event types, you might want to handle them differently
-        // For now, we'll just store them as-is if a subject exists
-        if let Some(subject) = &event.subject {
-            let resource_type = extract_resource_type_from_subject(subject);
-            println!(
-                "[handlers] non-json-commit event: storing event.id={} as resource type={}",
-                event.id, resource_type
-            );
-            state
-                .storage
-                .store_resource(&event.id, resource_type, data)
-                .await?;
-        } else {
-            println!(
-                "[handlers] non-json-commit event without subject: event.id={}",
-                event.id
-            );
+            match rx.recv().await {
+                Ok(event) => {
+                    if let Some(seq) = &event.sequence {
+                        if last_sent.as_ref().is_some_and(|last| seq <= last) {
+                            continue;
+                        }
+                        if Some(seq) != next_sequence(last_sent.as_deref()).as_ref() {
+                            catching_up = true;
+                            continue;
+                        }
+                        last_sent = Some(seq.clone());
+                    }
+                    yield DeltaMessage::Delta(Box::new(event));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    eprintln!(
+                        "[handlers] delta stream lagged by {} events, re-reading after seq={:?}",
+                        skipped, last_sent
+                    );
+                    catching_up = true;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
         }
     }
-
-    Ok(())
 }
 
-/// Extract resource type from schema URL
-fn extract_resource_type_from_schema(schema: &str) -> &str {
-    if schema.contains("Issue") {
-        "issue"
-    } else if schema.contains("Comment") {
-        "comment"
-    } else if schema.contains("Task") {
-        "task"
-    } else if schema.contains("Planning") {
-        "planning"
-    } else if schema.contains("Document") {
-        "document"
-    } else {
-        "unknown"
-    }
-}
-
-/// Extract resource type from subject
-fn extract_resource_type_from_subject(subject: &str) -> &str {
-    if subject.contains("issue") {
-        "issue"
-    } else if subject.contains("comment") {
-        "comment"
-    } else if subject.contains("task") {
-        "task"
-    } else if subject.contains("planning") {
-        "planning"
-    } else if subject.contains("document") {
-        "document"
-    } else {
-        "unknown"
+/// Sequence key of the event stored after `seq`, or of the first event.
+fn next_sequence(seq: Option<&str>) -> Option<String> {
+    match seq {
+        Some(seq) => seq.parse::<u128>().ok().map(|n| sequence_key(n + 1)),
+        None => Some(sequence_key(1)),
     }
 }
 
-/// Apply JSON Merge Patch (RFC 7396)
-fn apply_json_merge_patch(target: &mut Value, patch: &Value) {
-    if !patch.is_object() {
-        *target = patch.clone();
-        return;
-    }
+/// GET /events/{id} - Fetch a single stored CloudEvent by its id
+pub async fn get_event(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<CloudEvent>, Problem> {
+    let event = state.storage.get_event(&id).await?;
 
-    if !target.is_object() {
-        *target = serde_json::json!({});
+    match event {
+        Some(event) => Ok(Json(event)),
+        None => Err(StorageError::NotFound(format!("event {}", id)).into()),
     }
+}
 
-    let target_obj = target.as_object_mut().unwrap();
-    let patch_obj = patch.as_object().unwrap();
+/// Response for query endpoint
+#[derive(Debug, Serialize)]
+pub struct QueryResponse {
+    pub query: String,
+    pub results: Vec<SearchResult>,
+    /// Number of results in this page
+    pub count: usize,
+    /// Number of matching documents across all pages
+    pub total: usize,
+    pub offset: usize,
+    /// Offset of the next page, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+    /// Matching documents per status and type
+    pub facets: BTreeMap<String, BTreeMap<String, u64>>,
+}
 
-    for (key, value) in patch_obj {
-        if value.is_null() {
-            target_obj.remove(key);
-        } else if value.is_object() && target_obj.contains_key(key) {
-            let mut target_value = target_obj.get(key).unwrap().clone();
-            apply_json_merge_patch(&mut target_value, value);
-            target_obj.insert(key.clone(), target_value);
-        } else {
-            target_obj.insert(key.clone(), value.clone());
+/// POST /events - Handle incoming CloudEvents (Command + Sync)
+/// This is where resources are created, updated, and deleted
+///
+/// Returns `202 Accepted` for new events. Re-posting an event with a `source` + `id` that was
+/// already accepted returns `200 OK` with the originally stored event and sequence.
+pub async fn handle_event(
+    State(state): State<AppState>,
+    ValidCloudEvent(mut event): ValidCloudEvent,
+) -> Result<Response, Problem> {
+    // Store the event, apply it to the resources and get the assigned server sequence key
+    let (outcome, committed) = state
+        .storage
+        .store_event_committed(&event)
+        .await
+        .map_err(|e| Problem::from(e).with_event_id(&event.id))?;
+
+    let seq_key = match outcome {
+        StoreOutcome::Stored(seq_key) => seq_key,
+        StoreOutcome::Duplicate(original_seq) => {
+            // A retry of an event we already accepted: answer with the original event and
+            // sequence without processing or broadcasting it again.
+            let original = state
+                .storage
+                .get_event_by_sequence(&original_seq)
+                .await?
+                .unwrap_or_else(|| {
+                    event.sequence = Some(original_seq);
+                    event
+                });
+            return Ok((StatusCode::OK, Json(original)).into_response());
         }
-    }
+    };
+
+    // Attach the assigned sequence to the CloudEvent so clients can use it for ordering/pagination
+    event.sequence = Some(seq_key.clone());
+
+    publish_event(&state, event.clone(), committed);
+
+    Ok((StatusCode::ACCEPTED, Json(event)).into_response())
+}
+
+/// Announce a stored event (with its sequence attached): broadcast it to SSE subscribers and
+/// notify push subscribers without waiting for the push services.
+fn publish_event(state: &AppState, event: CloudEvent, committed: CommittedState) {
+    let _ = state.tx.send(event.clone());
+    state.push.dispatch(event, committed);
+}
+
+/// Outcome of validating a CloudEvent without storing it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Whether `POST /events` would accept the event (no error findings)
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    /// Every error and warning, CloudEvents 1.0 and NL-GOV profile alike
+    pub findings: Vec<Finding>,
+}
+
+/// POST /events/validate - Check a CloudEvent against CloudEvents 1.0 and the NL-GOV profile
+/// without storing it
+pub async fn validate_event(ApiJson(body): ApiJson<Value>) -> Json<ValidationReport> {
+    let findings = validation::validate_cloudevent(&body);
+    Json(ValidationReport {
+        valid: !validation::has_errors(&findings),
+        event_id: body.get("id").and_then(Value::as_str).map(str::to_string),
+        findings,
+    })
 }
 
 /// GET /resources - List all resources (paginated)
 pub async fn list_resources(
     State(state): State<AppState>,
-    Query(params): Query<ListParams>,
-) -> Result<Json<Vec<ResourceResponse>>, StatusCode> {
+    ApiQuery(params): ApiQuery<ListParams>,
+) -> Result<Json<Vec<ResourceResponse>>, Problem> {
     let resources = state
         .storage
         .list_resources(params.offset, params.limit)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to list resources: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .await?;
 
     let response: Vec<ResourceResponse> = resources
         .into_iter()
@@ -471,85 +763,203 @@ pub async fn list_resources(
     Ok(Json(response))
 }
 
+/// Query parameters for reading a resource as of an earlier point in the event log
+#[derive(Debug, Deserialize)]
+pub struct ResourceAtParams {
+    /// Sequence (e.g. "42" or "00000000000000000042") to reconstruct the resource at
+    #[serde(default)]
+    pub at_seq: Option<String>,
+    /// RFC 3339 timestamp to reconstruct the resource at
+    #[serde(default)]
+    pub at_time: Option<String>,
+}
+
+impl ResourceAtParams {
+    /// Parse the requested point in time. `Ok(None)` means the live projection.
+    #[allow(clippy::result_large_err)] // the problem becomes the response body
+    fn as_of(&self) -> Result<Option<AsOf>, Problem> {
+        match (&self.at_seq, &self.at_time) {
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(Problem::invalid_parameter(
+                "at_time",
+                "use either at_seq or at_time, not both",
+            )),
+            (Some(seq), None) => normalize_sequence(seq)
+                .map(|seq| Some(AsOf::Sequence(seq)))
+                .ok_or_else(|| {
+                    Problem::invalid_parameter(
+                        "at_seq",
+                        format!("expected a sequence number, got `{}`", seq),
+                    )
+                }),
+            (None, Some(time)) => chrono::DateTime::parse_from_rfc3339(time)
+                .map(|t| Some(AsOf::Time(t.with_timezone(&chrono::Utc))))
+                .map_err(|_| {
+                    Problem::invalid_parameter(
+                        "at_time",
+                        format!("expected an RFC 3339 time, got `{}`", time),
+                    )
+                }),
+        }
+    }
+}
+
 /// GET /resources/:id - Get a specific resource
+///
+/// With `?at_seq=` or `?at_time=` the resource is reconstructed from its event history
+/// as it was at that point instead of read from the live projection.
 pub async fn get_resource(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
-    let resource = state.storage.get_resource(&id).await.map_err(|e| {
-        eprintln!("Failed to get resource: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    ApiQuery(params): ApiQuery<ResourceAtParams>,
+) -> Result<Json<Value>, Problem> {
+    let resource = match params.as_of()? {
+        Some(as_of) => state.storage.resource_at(&id, &as_of).await,
+        None => state.storage.get_resource(&id).await,
+    }?;
 
     match resource {
         Some(data) => Ok(Json(data)),
-        None => Err(StatusCode::NOT_FOUND),
+        None => Err(StorageError::NotFound(format!("resource {}", id)).into()),
+    }
+}
+
+/// GET /resources/:id/history - List the CloudEvents that changed a resource, oldest first.
+/// Each JSONCommit carries the actor, timestamp and patch or data that was applied.
+pub async fn get_resource_history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<CloudEvent>>, Problem> {
+    let history = state.storage.resource_history(&id).await?;
+
+    if history.is_empty() {
+        return Err(StorageError::NotFound(format!("resource {}", id)).into());
     }
+
+    Ok(Json(history))
 }
 
 /// DELETE /resources/:id - Delete a specific resource
+///
+/// The deletion is stored as a JSONCommit event with `deleted: true`, which is streamed and
+/// pushed like any other event.
 pub async fn delete_resource(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<StatusCode, StatusCode> {
-    state.storage.delete_resource(&id).await.map_err(|e| {
-        eprintln!("Failed to delete resource: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+) -> Result<StatusCode, Problem> {
+    let (event, committed) = state.storage.delete_resource(&id).await?;
+    publish_event(&state, event, committed);
 
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// GET /query - Search resources using full-text search
+/// POST /admin/rebuild-projection - Regenerate the resources table by replaying the event log.
+/// Returns counts and the events that failed to apply.
+pub async fn rebuild_projection(
+    State(state): State<AppState>,
+) -> Result<Json<RebuildReport>, Problem> {
+    let report = state.storage.rebuild_projection().await?;
+
+    Ok(Json(report))
+}
+
+/// POST /admin/reindex - Drop the search index and rebuild it from the database.
+pub async fn reindex(State(state): State<AppState>) -> Result<Json<ReindexReport>, Problem> {
+    let report = state.storage.reindex().await?;
+
+    Ok(Json(report))
+}
+
+/// GET /admin/index-check - Report ids present in the database but not in the search index,
+/// and vice versa.
+pub async fn check_index(State(state): State<AppState>) -> Result<Json<IndexCheckReport>, Problem> {
+    let report = state.storage.check_index().await?;
+
+    Ok(Json(report))
+}
+
+/// GET /query - Search resources using full-text search.
+/// Supports field-scoped queries (`title:paspoort`), `type=`, `status=` and `from=`/`to=`
+/// filters, `sort=recent`, `offset` paging and `consistent=true` (read-your-writes).
+/// Returns scored, highlighted hits plus the total and facet counts.
 pub async fn query_resources(
     State(state): State<AppState>,
-    Query(params): Query<QueryParams>,
-) -> Result<Json<QueryResponse>, StatusCode> {
-    let results = state
-        .storage
-        .search(&params.q, params.limit)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to search: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    ApiQuery(params): ApiQuery<QueryParams>,
+) -> Result<Json<QueryResponse>, Problem> {
+    if params.consistent {
+        state.storage.flush_index().await?;
+    }
+    let query = params.into_search_query()?;
+    let results = state.storage.search(&query).await.map_err(|e| match e {
+        StorageError::InvalidQuery(_) => Problem::from(e).with_field("q"),
+        e => Problem::from(e),
+    })?;
 
-    let count = results.len();
+    let count = results.hits.len();
 
     Ok(Json(QueryResponse {
-        query: params.q,
-        results,
+        query: query.q,
+        results: results.hits,
         count,
+        total: results.total,
+        offset: query.offset,
+        next_offset: results.next_offset,
+        facets: results.facets,
+    }))
+}
+
+/// Query parameters for title suggestions
+#[derive(Debug, Deserialize)]
+pub struct SuggestParams {
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_suggest_limit")]
+    pub limit: usize,
+}
+
+fn default_suggest_limit() -> usize {
+    10
+}
+
+impl SuggestParams {
+    #[allow(clippy::result_large_err)] // the problem becomes the response body
+    fn validate(&self) -> Result<(), Problem> {
+        check_limit(self.limit, MAX_SUGGEST_LIMIT)
+    }
+}
+
+/// Response for the suggest endpoint
+#[derive(Debug, Serialize)]
+pub struct SuggestResponse {
+    pub prefix: String,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// GET /query/suggest - Complete resource titles for search-as-you-type.
+pub async fn suggest(
+    State(state): State<AppState>,
+    ApiQuery(params): ApiQuery<SuggestParams>,
+) -> Result<Json<SuggestResponse>, Problem> {
+    params.validate()?;
+    let suggestions = state.storage.suggest(&params.prefix, params.limit).await?;
+
+    Ok(Json(SuggestResponse {
+        prefix: params.prefix,
+        suggestions,
     }))
 }
 
 /// GET /debug/db - Return counts and sample ids of events and resources for diagnostics.
 /// Use this to verify what is persisted on disk.
-pub async fn debug_db(
-    State(state): State<AppState>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+pub async fn debug_db(State(state): State<AppState>) -> Result<Json<serde_json::Value>, Problem> {
     // Gather a reasonably sized sample (limit to avoid heavy work)
     let sample_limit = 50usize;
 
     // Events
-    let events = state
-        .storage
-        .list_events(0, sample_limit)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to list events for debug: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let events = state.storage.list_events(0, sample_limit).await?;
 
     // Resources
-    let resources = state
-        .storage
-        .list_resources(0, sample_limit)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to list resources for debug: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let resources = state.storage.list_resources(0, sample_limit).await?;
 
     // Build summaries
     let event_count = events.len();
@@ -571,55 +981,377 @@ pub async fn debug_db(
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_apply_json_merge_patch() {
-        let mut target = serde_json::json!({
-            "title": "Old Title",
-            "status": "open",
-            "nested": {
-                "a": 1,
-                "b": 2
-            }
-        });
-
-        let patch = serde_json::json!({
-            "title": "New Title",
-            "status": null,
-            "nested": {
-                "b": 3,
-                "c": 4
-            }
-        });
+    fn test_event(n: usize) -> CloudEvent {
+        CloudEvent {
+            specversion: "1.0".to_string(),
+            id: format!("event-{}", n),
+            source: "test".to_string(),
+            subject: Some("1".to_string()),
+            event_type: "test.event".to_string(),
+            time: Some(chrono::Utc::now().to_rfc3339()),
+            datacontenttype: Some("application/json".to_string()),
+            dataschema: None,
+            dataref: None,
+            sequence: None,
+            sequencetype: None,
+            data: Some(serde_json::json!({"n": n})),
+        }
+    }
 
-        apply_json_merge_patch(&mut target, &patch);
+    async fn store_test_event(storage: &Storage, n: usize) -> CloudEvent {
+        let mut event = test_event(n);
+        let outcome = storage.store_event(&event).await.unwrap();
+        event.sequence = Some(outcome.sequence().to_string());
+        event
+    }
 
-        assert_eq!(target["title"], "New Title");
-        assert!(!target.as_object().unwrap().contains_key("status"));
-        assert_eq!(target["nested"]["a"], 1);
-        assert_eq!(target["nested"]["b"], 3);
-        assert_eq!(target["nested"]["c"], 4);
+    #[test]
+    fn test_suggest_limit_is_bounded() {
+        let params = |limit: usize| SuggestParams {
+            prefix: "pasp".to_string(),
+            limit,
+        };
+        assert!(params(default_suggest_limit()).validate().is_ok());
+        assert!(params(MAX_SUGGEST_LIMIT).validate().is_ok());
+        for limit in [0, MAX_SUGGEST_LIMIT + 1] {
+            let problem = params(limit).validate().unwrap_err();
+            assert_eq!(problem.status, 400);
+            assert_eq!(problem.field.as_deref(), Some("limit"));
+        }
     }
 
     #[test]
-    fn test_extract_resource_type_from_schema() {
+    fn test_query_params_parse_dates_and_sort() {
+        let params: QueryParams =
+            serde_json::from_value(serde_json::json!({"q": "paspoort", "from": "2024-01-08", "to": "2024-01-14", "sort": "recent"}))
+                .unwrap();
+        let query = params.into_search_query().unwrap();
         assert_eq!(
-            extract_resource_type_from_schema("http://example.com/Issue"),
-            "issue"
+            query.from.unwrap().to_rfc3339(),
+            "2024-01-08T00:00:00+00:00"
         );
+        assert_eq!(query.to.unwrap().to_rfc3339(), "2024-01-14T23:59:59+00:00");
+        assert_eq!(query.sort, SearchSort::Recent);
+
+        let params: QueryParams =
+            serde_json::from_value(serde_json::json!({"from": "2024-01-08T10:00:00+01:00"}))
+                .unwrap();
+        let query = params.into_search_query().unwrap();
         assert_eq!(
-            extract_resource_type_from_schema("http://example.com/Comment"),
-            "comment"
+            query.from.unwrap().to_rfc3339(),
+            "2024-01-08T09:00:00+00:00"
         );
+        assert_eq!(query.sort, SearchSort::Relevance);
+
+        for bad in [
+            serde_json::json!({"from": "last week"}),
+            serde_json::json!({"sort": "oldest"}),
+            serde_json::json!({"fuzziness": 3}),
+            serde_json::json!({"limit": 0}),
+            serde_json::json!({"limit": 10001}),
+            serde_json::json!({"offset": 100_001}),
+        ] {
+            let params: QueryParams = serde_json::from_value(bad).unwrap();
+            let problem = params.into_search_query().unwrap_err();
+            assert_eq!(problem.status, 400);
+            assert!(problem.field.is_some());
+        }
+    }
+
+    #[test]
+    fn test_last_event_id_is_normalized() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(last_event_id(&headers), None);
+
+        headers.insert("last-event-id", "42".parse().unwrap());
         assert_eq!(
-            extract_resource_type_from_schema("http://example.com/Task"),
-            "task"
+            last_event_id(&headers).as_deref(),
+            Some("00000000000000000042")
         );
+
+        headers.insert("last-event-id", "not-a-sequence".parse().unwrap());
+        assert_eq!(last_event_id(&headers), None);
     }
 
-    #[test]
-    fn test_extract_resource_type_from_subject() {
-        assert_eq!(extract_resource_type_from_subject("issue/123"), "issue");
-        assert_eq!(extract_resource_type_from_subject("comment/456"), "comment");
-        assert_eq!(extract_resource_type_from_subject("unknown/789"), "unknown");
+    #[tokio::test]
+    async fn test_delta_stream_resumes_without_gaps_or_duplicates() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (tx, rx) = broadcast::channel(16);
+
+        let first = store_test_event(&storage, 1).await;
+        store_test_event(&storage, 2).await;
+        let third = store_test_event(&storage, 3).await;
+
+        // The third event is broadcast after the subscription as well as read from storage.
+        tx.send(third).unwrap();
+        let live = store_test_event(&storage, 4).await;
+        tx.send(live).unwrap();
+        drop(tx);
+
+        let ids = collect_delta_ids(delta_stream(storage, rx, first.sequence)).await;
+
+        assert_eq!(ids, vec!["event-2", "event-3", "event-4"]);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_is_followed_by_live_deltas() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (tx, rx) = broadcast::channel(16);
+
+        store_test_event(&storage, 1).await;
+        let second = store_test_event(&storage, 2).await;
+
+        // What `event_stream` sends as the snapshot and resumes the deltas from
+        let snapshot = storage.list_events_after(None, 100).await.unwrap();
+        let last_seq = snapshot.last().and_then(|e| e.sequence.clone());
+        assert_eq!(last_seq, second.sequence);
+
+        // An event in the snapshot that is broadcast as well is not sent again
+        tx.send(second).unwrap();
+        let live = store_test_event(&storage, 3).await;
+        tx.send(live).unwrap();
+        drop(tx);
+
+        let ids = collect_delta_ids(delta_stream(storage, rx, last_seq)).await;
+
+        assert_eq!(ids, vec!["event-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_delta_stream_recovers_from_broadcast_lag() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (tx, rx) = broadcast::channel(2);
+
+        // Broadcast more events than the channel holds so the subscriber lags.
+        for n in 1..=6 {
+            let event = store_test_event(&storage, n).await;
+            tx.send(event).unwrap();
+        }
+        drop(tx);
+
+        let ids = collect_delta_ids(delta_stream(storage, rx, None)).await;
+
+        let expected: Vec<String> = (1..=6).map(|n| format!("event-{}", n)).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_delta_stream_sends_out_of_order_broadcasts_in_sequence() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (tx, rx) = broadcast::channel(16);
+
+        let first = store_test_event(&storage, 1).await;
+        let second = store_test_event(&storage, 2).await;
+        let third = store_test_event(&storage, 3).await;
+
+        // The second request broadcasts before the first
+        tx.send(second).unwrap();
+        tx.send(first).unwrap();
+        tx.send(third).unwrap();
+        drop(tx);
+
+        let ids = collect_delta_ids(delta_stream(storage, rx, None)).await;
+
+        assert_eq!(ids, vec!["event-1", "event-2", "event-3"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_posts_stream_in_sequence_order() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (tx, rx) = broadcast::channel(256);
+        let state = AppState::new(storage.clone(), tx);
+        let deltas = delta_stream(storage, rx, None);
+
+        let posts: Vec<_> = (1..=50)
+            .map(|n| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    handle_event(State(state), ValidCloudEvent(test_event(n)))
+                        .await
+                        .unwrap();
+                })
+            })
+            .collect();
+        for post in posts {
+            post.await.unwrap();
+        }
+        drop(state);
+
+        let sequences: Vec<String> = deltas
+            .map(|message| match message {
+                DeltaMessage::Delta(event) => event.sequence.unwrap(),
+                DeltaMessage::Resync { .. } => "resync".to_string(),
+            })
+            .collect()
+            .await;
+        let expected: Vec<String> = (1..=50).map(sequence_key).collect();
+        assert_eq!(sequences, expected);
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_ends_with_shutdown_event() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        store_test_event(&storage, 1).await;
+        let (tx, _) = broadcast::channel(16);
+        let state = AppState::new(storage, tx);
+
+        let sse = event_stream(&state, &HeaderMap::new(), None, 100)
+            .await
+            .unwrap();
+        state.shutdown.send_replace(true);
+
+        // The body only completes because the stream ends after the shutdown event
+        let body = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            axum::body::to_bytes(sse.into_response().into_body(), usize::MAX),
+        )
+        .await
+        .expect("stream did not end on shutdown")
+        .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(sse_field(&body, "event"), ["snapshot", "shutdown"]);
+        let retry: Vec<u64> = sse_field(&body, "retry")
+            .iter()
+            .map(|ms| ms.parse().unwrap())
+            .collect();
+        assert_eq!(retry, [5000]);
+    }
+
+    /// Values of the `name` field in an SSE body. The space after the colon is optional in
+    /// the SSE format, and axum versions differ in writing it.
+    fn sse_field<'a>(body: &'a str, name: &str) -> Vec<&'a str> {
+        body.lines()
+            .filter_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(|value| value.strip_prefix(' ').unwrap_or(value))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_rejected_events_are_problem_json() {
+        let request = |content_type: &str, body: &str| {
+            Request::builder()
+                .method("POST")
+                .header(header::CONTENT_TYPE, content_type)
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap()
+        };
+        let extract = |req| async move {
+            ApiJson::<CloudEvent>::from_request(req, &())
+                .await
+                .map(|_| ())
+                .unwrap_err()
+        };
+
+        let problem = extract(request(
+            "application/json",
+            r#"{"id": "evt-1", "source": "test"}"#,
+        ))
+        .await;
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.field.as_deref(), Some("specversion"));
+        assert_eq!(problem.event_id.as_deref(), Some("evt-1"));
+
+        let problem = extract(request("application/json", "{")).await;
+        assert_eq!(problem.problem_type, "/problems/malformed-json");
+
+        let problem = extract(request("text/plain", "{}")).await;
+        assert_eq!(problem.status, 415);
+        // Structured-mode CloudEvents are JSON too
+        let problem = extract(request("application/cloudevents+json", "{}")).await;
+        assert_eq!(problem.status, 422);
+
+        // Events posted to /events must conform to CloudEvents 1.0, and every error is listed
+        let problem = ValidCloudEvent::from_request(
+            request(
+                "application/cloudevents+json",
+                r#"{"specversion": "1.0", "id": "evt-2", "source": "test", "type": "json.commit",
+                    "time": "yesterday", "Actor": "alice"}"#,
+            ),
+            &(),
+        )
+        .await
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(problem.problem_type, "/problems/invalid-cloudevent");
+        assert_eq!(problem.event_id.as_deref(), Some("evt-2"));
+        let attributes: Vec<&str> = problem
+            .findings
+            .iter()
+            .map(|f| f.attribute.as_str())
+            .collect();
+        assert_eq!(attributes, ["time", "Actor"]);
+        assert_eq!(problem.field.as_deref(), Some("time"));
+
+        // A malformed JSONCommit is rejected by the storage layer
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (tx, _) = broadcast::channel(16);
+        let mut event = test_event(1);
+        event.event_type = "json.commit".to_string();
+        event.data = Some(serde_json::json!({"schema": "Issue"}));
+        let state = AppState::new(storage, tx);
+        let problem = handle_event(State(state.clone()), ValidCloudEvent(event.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.problem_type, "/problems/invalid-event");
+        assert_eq!(problem.event_id.as_deref(), Some("event-1"));
+        assert_eq!(problem.field.as_deref(), Some("data.resource_id"));
+
+        // So is a commit whose resource does not match its schema, listing every violation
+        event.data = Some(serde_json::json!({
+            "schema": "http://localhost:8000/schemas/Issue",
+            "resource_id": "issue-1",
+            "resource_data": {"status": "open"}
+        }));
+        let problem = handle_event(State(state), ValidCloudEvent(event))
+            .await
+            .unwrap_err();
+        assert_eq!(problem.problem_type, "/problems/schema-violation");
+        assert_eq!(problem.violations.len(), 1);
+        assert_eq!(problem.violations[0].path, "/title");
+    }
+
+    #[tokio::test]
+    async fn test_validate_event_reports_all_findings() {
+        let Json(report) = validate_event(ApiJson(serde_json::json!({
+            "specversion": "1.0",
+            "id": "evt-1",
+            "source": "frontend-create",
+            "type": "json.commit",
+            "time": "2024-01-15T10:30:00+01:00",
+            "datacontenttype": "application/json",
+            "data": {"resource_id": "1"}
+        })))
+        .await;
+        assert!(report.valid, "{:?}", report.findings);
+        assert_eq!(report.event_id.as_deref(), Some("evt-1"));
+        // NL-GOV recommendations are reported, but do not make the event invalid
+        let attributes: Vec<&str> = report
+            .findings
+            .iter()
+            .map(|f| f.attribute.as_str())
+            .collect();
+        assert_eq!(attributes, ["id", "source"]);
+
+        let Json(report) = validate_event(ApiJson(serde_json::json!({"specversion": "2.0"}))).await;
+        assert!(!report.valid);
+        assert!(report.findings.len() >= 4, "{:?}", report.findings);
+    }
+
+    async fn collect_delta_ids(stream: impl Stream<Item = DeltaMessage>) -> Vec<String> {
+        stream
+            .map(|message| match message {
+                DeltaMessage::Delta(event) => event.id,
+                DeltaMessage::Resync { .. } => "resync".to_string(),
+            })
+            .collect()
+            .await
     }
 }
@@ -33,7 +33,7 @@ const STATUS_CHANGE_SCHEMA: &str = "http://localhost:8000/schemas/StatusChange";
 const DOCUMENT_SCHEMA: &str = "http://localhost:8000/schemas/Document";
 
 // Issue templates for initial data generation
-const ISSUE_TEMPLATES: &[(&str, &str, Option<&str>)] = &[
+pub(crate) const ISSUE_TEMPLATES: &[(&str, &str, Option<&str>)] = &[
     (
         "Nieuw paspoort aanvragen",
         "Burger wil nieuw paspoort aanvragen",
@@ -632,94 +632,6 @@ pub fn apply_merge_patch(target: &mut Value, patch: &Value) {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_generate_initial_data() {
-        let (events, issues) = generate_initial_data();
-
-        // Should have events for creation, patches, and deletes
-        assert!(events.len() > 10);
-
-        // Should have 8 issues remaining (10 created, 2 deleted)
-        assert_eq!(issues.len(), 8);
-
-        // All events should be valid CloudEvents
-        for event in &events {
-            assert!(event.get("specversion").is_some());
-            assert!(event.get("id").is_some());
-            assert!(event.get("source").is_some());
-            assert!(event.get("type").is_some());
-        }
-    }
-
-    #[test]
-    fn test_apply_merge_patch() {
-        let mut target = json!({
-            "title": "Originele Zaak",
-            "status": "open",
-            "assignee": "john@gemeente.nl"
-        });
-
-        let patch = json!({
-            "status": "closed",
-            "assignee": null,
-            "resolution": "fixed"
-        });
-
-        apply_merge_patch(&mut target, &patch);
-
-        assert_eq!(target["status"], "closed");
-        assert_eq!(target["resolution"], "fixed");
-        assert_eq!(target["assignee"], Value::Null);
-        assert_eq!(target["title"], "Originele Zaak"); // unchanged
-    }
-
-    #[test]
-    fn test_generate_demo_event() {
-        let mut issues = HashMap::new();
-        issues.insert(
-            "1".to_string(),
-            json!({
-                "id": "1",
-                "title": "Test Zaak",
-                "status": "open"
-            }),
-        );
-        issues.insert(
-            "2".to_string(),
-            json!({
-                "id": "2",
-                "title": "Andere Zaak",
-                "status": "closed"
-            }),
-        );
-
-        let demo_event = generate_demo_event(&issues);
-        assert!(demo_event.is_some());
-
-        let event = demo_event.unwrap();
-        assert_eq!(event["specversion"], "1.0");
-        assert!(event.get("id").is_some());
-        assert!(event.get("source").is_some());
-        assert!(event.get("type").is_some());
-        assert!(event.get("time").is_some());
-
-        // Should use the json.commit event type
-        let event_type = event["type"].as_str().unwrap();
-        assert_eq!(event_type, "json.commit");
-    }
-
-    #[test]
-    fn test_generate_demo_event_empty_issues() {
-        let empty_issues = HashMap::new();
-        let demo_event = generate_demo_event(&empty_issues);
-        assert!(demo_event.is_none());
-    }
-}
-
 /// Generate a random demo CloudEvent that modifies an existing issue
 pub fn generate_demo_event(existing_issues: &HashMap<String, Value>) -> Option<Value> {
     if existing_issues.is_empty() {
@@ -741,6 +653,11 @@ pub fn generate_demo_event(existing_issues: &HashMap<String, Value>) -> Option<V
     }
 }
 
+/// Whether a stored resource is an issue, i.e. has the fields the Issue schema requires.
+pub fn is_issue(resource: &Value) -> bool {
+    resource.get("title").is_some() && resource.get("status").is_some()
+}
+
 /// Helper function to create a base CloudEvent structure
 fn create_base_cloud_event(source: &str, subject: Option<&str>, datacontenttype: &str) -> Value {
     let mut event = json!({
@@ -802,13 +719,10 @@ fn create_cloud_event(
 fn generate_random_patch_event(issue_id: &str) -> Value {
     let patch_operations = [
         json!({"status": "open"}),
-        json!({"status": "in_behandeling"}),
-        json!({"status": "wachtend_op_informatie"}),
-        json!({"status": "in_beoordeling"}),
-        json!({"status": "gereed_voor_besluit"}),
-        json!({"status": "afgesloten", "resolution": "toegekend"}),
-        json!({"status": "afgesloten", "resolution": "afgewezen"}),
-        json!({"status": "afgesloten", "resolution": "ingetrokken"}),
+        json!({"status": "in_progress"}),
+        json!({"status": "closed", "resolution": "toegekend"}),
+        json!({"status": "closed", "resolution": "afgewezen"}),
+        json!({"status": "closed", "resolution": "ingetrokken"}),
         json!({"assignee": "alice@gemeente.nl"}),
         json!({"assignee": "bob@gemeente.nl"}),
         json!({"assignee": "specialist@gemeente.nl"}),
@@ -877,26 +791,23 @@ fn generate_create_event_with_data(
 }
 
 fn generate_delete_event_with_data(issue_id: &str, reason: &str) -> Value {
-    // For deletion, we use the deleted field directly
     // When deleted is true, the entire resource is removed from the store
-    let delete_data = json!({
-        "schema": ISSUE_SCHEMA,
-        "resource_id": issue_id,
-        "actor": "system@gemeente.nl",
-        "timestamp": chrono::Utc::now().to_rfc3339(),
-        "deleted": true,
-        "deletion_reason": reason
-    });
-
-    create_cloud_event(
+    let mut delete_event = create_cloud_event(
         DEFAULT_SOURCE,
         Some(issue_id),
         CONTENT_TYPE_JSON,
         issue_id,
-        Some(&delete_data),
+        None,
         None,
         ISSUE_SCHEMA,
-    )
+    );
+    if let Some(data) = delete_event.get_mut("data") {
+        data["actor"] = json!("system@gemeente.nl");
+        data["timestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        data["deleted"] = json!(true);
+        data["deletion_reason"] = json!(reason);
+    }
+    delete_event
 }
 
 fn generate_random_comment_event(issue_id: &str) -> Value {
@@ -1188,9 +1099,9 @@ fn generate_planning_event_with_data(
     let base_date = Utc::now();
 
     for (index, (moment_title, status)) in moments_data.iter().enumerate() {
-        let days_offset = match status {
-            &"completed" => -(fastrand::i64(5..=30)), // Past dates
-            &"current" => fastrand::i64(-2..=2),      // Around now
+        let days_offset = match *status {
+            "completed" => -(fastrand::i64(5..=30)), // Past dates
+            "current" => fastrand::i64(-2..=2),      // Around now
             _ => fastrand::i64(7..=(30 + index as i64 * 14)), // Future dates
         };
 
@@ -1330,3 +1241,124 @@ pub fn json_to_cloudevent(json_event: &Value) -> Option<CloudEvent> {
         data: json_event.get("data").cloned(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_initial_data() {
+        let (events, issues) = generate_initial_data();
+
+        // Should have events for creation, patches, and deletes
+        assert!(events.len() > 10);
+
+        // Should have 8 issues remaining (10 created, 2 deleted)
+        assert_eq!(issues.len(), 8);
+
+        // All events should be valid CloudEvents
+        for event in &events {
+            assert!(event.get("specversion").is_some());
+            assert!(event.get("id").is_some());
+            assert!(event.get("source").is_some());
+            assert!(event.get("type").is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initial_and_demo_events_match_schemas() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = crate::storage::Storage::new(temp_dir.path()).await.unwrap();
+
+        let (events, _) = generate_initial_data();
+        for event in &events {
+            let findings = crate::validation::validate_cloudevent(event);
+            assert!(!crate::validation::has_errors(&findings), "{:?}", findings);
+            let event = json_to_cloudevent(event).unwrap();
+            if let Err(e) = storage.store_event(&event).await {
+                panic!("initial event {} rejected: {}", event.id, e);
+            }
+        }
+
+        for _ in 0..50 {
+            let issues: HashMap<String, Value> = storage
+                .list_resources(0, 1000)
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|(_, data)| is_issue(data))
+                .collect();
+            let Some(event) = generate_demo_event(&issues) else {
+                break;
+            };
+            let event = json_to_cloudevent(&event).unwrap();
+            if let Err(e) = storage.store_event(&event).await {
+                panic!("demo event {:?} rejected: {}", event.data, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_merge_patch() {
+        let mut target = json!({
+            "title": "Originele Zaak",
+            "status": "open",
+            "assignee": "john@gemeente.nl"
+        });
+
+        let patch = json!({
+            "status": "closed",
+            "assignee": null,
+            "resolution": "fixed"
+        });
+
+        apply_merge_patch(&mut target, &patch);
+
+        assert_eq!(target["status"], "closed");
+        assert_eq!(target["resolution"], "fixed");
+        assert_eq!(target["assignee"], Value::Null);
+        assert_eq!(target["title"], "Originele Zaak"); // unchanged
+    }
+
+    #[test]
+    fn test_generate_demo_event() {
+        let mut issues = HashMap::new();
+        issues.insert(
+            "1".to_string(),
+            json!({
+                "id": "1",
+                "title": "Test Zaak",
+                "status": "open"
+            }),
+        );
+        issues.insert(
+            "2".to_string(),
+            json!({
+                "id": "2",
+                "title": "Andere Zaak",
+                "status": "closed"
+            }),
+        );
+
+        let demo_event = generate_demo_event(&issues);
+        assert!(demo_event.is_some());
+
+        let event = demo_event.unwrap();
+        assert_eq!(event["specversion"], "1.0");
+        assert!(event.get("id").is_some());
+        assert!(event.get("source").is_some());
+        assert!(event.get("type").is_some());
+        assert!(event.get("time").is_some());
+
+        // Should use the json.commit event type
+        let event_type = event["type"].as_str().unwrap();
+        assert_eq!(event_type, "json.commit");
+    }
+
+    #[test]
+    fn test_generate_demo_event_empty_issues() {
+        let empty_issues = HashMap::new();
+        let demo_event = generate_demo_event(&empty_issues);
+        assert!(demo_event.is_none());
+    }
+}
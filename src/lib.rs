@@ -1,8 +1,12 @@
 pub mod types;
-pub use types::{PushKeys, PushSubscription};
+pub use types::{PushDeliveryStats, PushFilters, PushKeys, PushSubscription};
 
+pub mod error;
 pub mod handlers;
 pub mod issues;
+pub mod projection;
 pub mod push;
 pub mod schemas;
 pub mod storage;
+pub mod validation;
+pub mod vapid;
@@ -1,16 +1,12 @@
-use sse_delta_snapshot::{handlers, issues, schemas};
+use sse_delta_snapshot::{error::Problem, handlers, issues, push, schemas};
 
-use futures_util::stream::{self, Stream};
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::path::PathBuf;
 
 #[cfg(feature = "shuttle")]
 use shuttle_axum::axum::{
     extract::State,
-    http::StatusCode,
-    response::sse::{Event, KeepAlive, Sse},
-    response::{Html, Response},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
     routing::{delete, get, post},
     Json, Router,
 };
@@ -18,23 +14,22 @@ use shuttle_axum::axum::{
 #[cfg(not(feature = "shuttle"))]
 use axum::{
     extract::State,
-    http::StatusCode,
-    response::sse::{Event, KeepAlive, Sse},
-    response::{Html, Response},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
     routing::{delete, get, post},
     serve, Json, Router,
 };
-use std::{convert::Infallible, sync::Arc, time::Duration};
+use std::{sync::Arc, time::Duration};
 use tokio::{
-    sync::{broadcast, RwLock},
+    sync::{broadcast, watch},
+    task::JoinHandle,
     time::sleep,
 };
-use tokio_stream::wrappers::BroadcastStream;
-use tokio_stream::StreamExt;
 use tower_http::services::ServeDir;
 use tower_http::{cors::CorsLayer, services::ServeFile};
 
-use sse_delta_snapshot::storage::Storage;
+use sse_delta_snapshot::storage::{IndexConfig, Storage};
+use sse_delta_snapshot::vapid::{VapidConfig, VapidKeys};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -42,48 +37,102 @@ pub struct AppState {
     pub storage: Arc<Storage>,
     // Broadcast deltas to all subscribers
     pub tx: broadcast::Sender<schemas::CloudEvent>,
+    // Push notifications for committed events, sent in the background
+    pub push: push::PushDispatcher,
     // Base URL for generating schema URLs
     #[allow(dead_code)]
     pub base_url: String,
-    // Push notification subscriptions
-    pub push_subscriptions: Arc<RwLock<Vec<sse_delta_snapshot::PushSubscription>>>,
+    // Flipped to `true` on shutdown; SSE streams and background tasks stop on it
+    pub shutdown: watch::Sender<bool>,
 }
 
 /// CloudEvent following the CloudEvents specification v1.0
 pub use schemas::CloudEvent;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct IncomingCloudEvent {
-    specversion: String,
-    id: String,
-    source: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    subject: Option<String>,
-    #[serde(rename = "type")]
-    event_type: String,
-    time: Option<String>,
-    datacontenttype: Option<String>,
-    data: Option<Value>,
-}
-
 #[cfg(feature = "shuttle")]
 #[shuttle_runtime::main]
 async fn main() -> shuttle_axum::ShuttleAxum {
-    let app = create_app().await;
+    let (app, _, _) = create_app().await;
     Ok(app.into())
 }
 
 #[cfg(not(feature = "shuttle"))]
 #[tokio::main]
 async fn main() {
-    let app = create_app().await;
+    let (app, state, background) = create_app().await;
     let addr = "0.0.0.0:8000";
     println!("→ http://{addr}");
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    serve(listener, app).await.unwrap();
+    serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal(state.shutdown.clone()))
+        .await
+        .unwrap();
+
+    // All connections are closed; let the background tasks finish their current run, as
+    // they share the storage
+    for task in background {
+        if let Err(e) = task.await {
+            eprintln!("Background task failed: {}", e);
+        }
+    }
+
+    // Send the push notifications still queued; their deliveries hold the storage while
+    // they record how they went
+    state.push.shutdown().await;
+
+    // Commit the search index and close the database
+    let AppState { storage, .. } = state;
+    match Arc::try_unwrap(storage) {
+        Ok(storage) => {
+            if let Err(e) = storage.close().await {
+                eprintln!("Failed to close storage: {}", e);
+            }
+        }
+        Err(storage) => {
+            eprintln!(
+                "Storage still has {} other owner(s) at shutdown; flushing the search index \
+                 only.",
+                Arc::strong_count(&storage) - 1
+            );
+            if let Err(e) = storage.flush_index().await {
+                eprintln!("Failed to flush search index: {}", e);
+            }
+        }
+    }
 }
 
-async fn create_app() -> Router {
+/// Wait for Ctrl+C or SIGTERM, then tell open SSE streams and background tasks to stop.
+/// Once this returns the server stops accepting connections and waits for open ones.
+#[cfg(not(feature = "shuttle"))]
+async fn shutdown_signal(shutdown: watch::Sender<bool>) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    println!("Shutting down: closing SSE streams and flushing storage");
+    shutdown.send_replace(true);
+}
+
+/// Build the router and the shared state. Also returns the background tasks, which stop
+/// once `state.shutdown` is set and must be awaited before the storage is closed.
+async fn create_app() -> (Router, AppState, Vec<JoinHandle<()>>) {
     if !std::path::Path::new("dist").exists() {
         panic!("Frontend dist folder is missing! Please build the frontend first with: cd frontend && pnpm run build");
     }
@@ -97,28 +146,61 @@ async fn create_app() -> Router {
         .map(PathBuf::from)
         .unwrap_or_else(|_| PathBuf::from("./data"));
 
-    let storage = Storage::new(&data_dir)
+    // Search index commit policy
+    let mut index_config = IndexConfig::default();
+    if let Some(ms) = std::env::var("SEARCH_COMMIT_INTERVAL_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|ms| *ms > 0)
+    {
+        index_config.commit_interval = Duration::from_millis(ms);
+    }
+    if let Some(n) = std::env::var("SEARCH_COMMIT_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+    {
+        index_config.commit_threshold = n;
+    }
+    if let Some(n) = std::env::var("SEARCH_QUEUE_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+    {
+        index_config.queue_capacity = n;
+    }
+
+    let storage = Storage::with_config(&data_dir, index_config)
         .await
         .expect("Failed to initialize storage");
 
     let (tx, _) = broadcast::channel(256);
 
+    let vapid_keys =
+        VapidKeys::load(&VapidConfig::from_env(), &data_dir).expect("Failed to load VAPID keys");
+
+    let storage = Arc::new(storage);
     let state = AppState {
-        storage: Arc::new(storage),
+        push: push::PushDispatcher::spawn(&storage, vapid_keys),
+        storage,
         tx: tx.clone(),
         base_url: base_url.clone(),
-        push_subscriptions: Arc::new(RwLock::new(Vec::new())),
+        shutdown: watch::channel(false).0,
     };
 
     // Initialize with demo data if storage is empty
     initialize_demo_data(&state).await;
 
     // Optional: emit demo events every 10s
+    let mut background = Vec::new();
     if std::env::var("DEMO").is_ok() {
         let demo_state = state.clone();
-        tokio::spawn(async move {
+        background.push(tokio::spawn(async move {
+            let mut shutdown = demo_state.shutdown.subscribe();
             loop {
-                sleep(Duration::from_secs(10)).await;
+                tokio::select! {
+                    _ = sleep(Duration::from_secs(10)) => {},
+                    _ = shutdown.wait_for(|down| *down) => break,
+                }
 
                 // Get current issues from storage
                 let resources = demo_state
@@ -129,35 +211,40 @@ async fn create_app() -> Router {
 
                 let mut issues_map = std::collections::HashMap::new();
                 for (id, data) in resources {
-                    if data.get("title").is_some() {
-                        // Likely an issue
+                    if issues::is_issue(&data) {
                         issues_map.insert(id, data);
                     }
                 }
 
                 // Generate a random demo event
                 if let Some(demo_event_json) = issues::generate_demo_event(&issues_map) {
-                    if let Some(cloud_event) = issues::json_to_cloudevent(&demo_event_json) {
+                    if let Ok(cloud_event) = handlers::ValidCloudEvent::from_value(demo_event_json)
+                    {
                         // Store via the proper handler
                         let _ = handlers::handle_event(
                             State(handlers::AppState {
                                 storage: demo_state.storage.clone(),
                                 tx: demo_state.tx.clone(),
-                                push_subscriptions: demo_state.push_subscriptions.clone(),
+                                push: demo_state.push.clone(),
+                                shutdown: demo_state.shutdown.clone(),
                             }),
-                            Json(cloud_event),
+                            cloud_event,
                         )
                         .await;
                     }
                 }
             }
-        });
+        }));
 
         // Reset all app state every 5 minutes
         let reset_state = state.clone();
-        tokio::spawn(async move {
+        background.push(tokio::spawn(async move {
+            let mut shutdown = reset_state.shutdown.subscribe();
             loop {
-                sleep(Duration::from_secs(300)).await;
+                tokio::select! {
+                    _ = sleep(Duration::from_secs(300)) => {},
+                    _ = shutdown.wait_for(|down| *down) => break,
+                }
 
                 let reset_time = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
                 println!("🔄 [{}] Resetting all app state...", reset_time);
@@ -167,14 +254,15 @@ async fn create_app() -> Router {
                 let complete_time = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
                 println!("✅ [{}] App state reset complete", complete_time);
             }
-        });
+        }));
     }
 
     // Create handler state
     let handler_state = handlers::AppState {
         storage: state.storage.clone(),
         tx: state.tx.clone(),
-        push_subscriptions: state.push_subscriptions.clone(),
+        push: state.push.clone(),
+        shutdown: state.shutdown.clone(),
     };
 
     // API routes with new storage-backed endpoints
@@ -187,18 +275,37 @@ async fn create_app() -> Router {
             "/events",
             get(handlers::get_or_stream_events).post(handlers::handle_event),
         )
+        .route("/events/validate", post(handlers::validate_event))
+        .route("/events/{id}", get(handlers::get_event))
         // Resource endpoints
         .route("/resources", get(handlers::list_resources))
         .route("/resources/{id}", get(handlers::get_resource))
         .route("/resources/{id}", delete(handlers::delete_resource))
+        .route(
+            "/resources/{id}/history",
+            get(handlers::get_resource_history),
+        )
         // Query endpoint with Tantivy search
         .route("/query", get(handlers::query_resources))
+        .route("/query/suggest", get(handlers::suggest))
         // Debug endpoint to inspect persisted DB counts and samples
         .route("/debug/db", get(handlers::debug_db))
+        // Admin endpoints to regenerate derived data (projection, search index)
+        .route(
+            "/admin/rebuild-projection",
+            post(handlers::rebuild_projection),
+        )
+        .route("/admin/reindex", post(handlers::reindex))
+        .route("/admin/index-check", get(handlers::check_index))
+        .route("/admin/push-stats", get(push::push_delivery_stats))
         // Legacy endpoints (can be removed later)
         .route("/reset/", post(reset_state_handler))
         .route("/schemas", get(crate::schemas::handle_get_schemas_index))
         .route("/schemas/{*name}", get(crate::schemas::handle_get_schema))
+        // Web Push subscriptions (used by the frontend's usePushNotifications)
+        .route("/api/push/subscribe", post(push::subscribe_push))
+        .route("/api/push/unsubscribe", post(push::unsubscribe_push))
+        .route("/api/push/vapid-public-key", get(push::vapid_public_key))
         .with_state(handler_state);
 
     // Combine API routes with static file serving
@@ -212,7 +319,7 @@ async fn create_app() -> Router {
         .fallback_service(ServeDir::new("dist").fallback(ServeFile::new("dist/index.html")))
         .layer(CorsLayer::permissive());
 
-    app
+    (app, state, background)
 }
 
 /// Initialize demo data in storage
@@ -220,66 +327,24 @@ async fn initialize_demo_data(state: &AppState) {
     let (initial_events, _) = issues::generate_initial_data();
 
     for event_json in initial_events {
-        if let Some(mut cloud_event) = issues::json_to_cloudevent(&event_json) {
-            // Store the event (persist to the DB) and obtain assigned sequence key
-            let seq_key = match state.storage.store_event(&cloud_event).await {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Failed to store initial event: {}", e);
-                    continue;
-                }
-            };
-
-            // Attach the assigned sequence to the CloudEvent so downstream processing and clients
-            // can rely on the server-assigned ordering.
-            cloud_event.sequence = Some(seq_key.clone());
-
-            // Build a handlers::AppState to reuse the same processing logic
-            // This ensures resources are created/updated using the same code path
-            // as runtime events.
-            let handlers_state = handlers::AppState {
-                storage: state.storage.clone(),
-                tx: state.tx.clone(),
-                push_subscriptions: state.push_subscriptions.clone(),
-            };
-
-            // Process the event to create/update resources using the handler logic.
-            // Log any error but continue with the remaining initial events.
-            if let Err(e) = handlers::process_event(&handlers_state, &cloud_event).await {
-                eprintln!("Failed to process initial event into resources: {}", e);
+        if let Some(cloud_event) = issues::json_to_cloudevent(&event_json) {
+            // Store the event (persist to the DB); this also creates/updates the resources
+            // using the same projection as runtime events.
+            if let Err(e) = state.storage.store_event(&cloud_event).await {
+                eprintln!("Failed to store initial event: {}", e);
             }
         }
     }
 }
 
-/* The helper `extract_resource_type` was removed from `main.rs` because resource-type
-detection is handled centrally in the handlers module. Keeping duplicate helpers
-here caused unused-function warnings. If a shared helper is desired in future,
-move it to a single common module (e.g., `handlers` or `types`) and import it where needed. */
-
 /// SSE handler for streaming events
 async fn sse_handler(
     State(state): State<handlers::AppState>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let rx = state.tx.subscribe();
-
-    // Get snapshot from storage
-    let snapshot_events = state.storage.list_events(0, 1000).await.unwrap_or_default();
-
-    let snapshot = serde_json::to_string(&snapshot_events).unwrap();
-
-    let stream = stream::once(async move { Ok(Event::default().event("snapshot").data(snapshot)) })
-        .chain(
-            BroadcastStream::new(rx)
-                .map(|msg| {
-                    let delta = msg.unwrap();
-                    let json = serde_json::to_string(&delta).unwrap();
-                    Event::default().event("delta").data(json)
-                })
-                .map(Ok),
-        );
-
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    headers: HeaderMap,
+) -> Result<Response, Problem> {
+    let sse = handlers::event_stream(&state, &headers, None, 1000).await?;
+
+    Ok(sse.into_response())
 }
 
 /// Reset state handler
@@ -289,22 +354,15 @@ async fn reset_state_handler(
     initialize_demo_data(&AppState {
         storage: state.storage.clone(),
         tx: state.tx.clone(),
+        push: state.push.clone(),
         base_url: "http://localhost:8000".to_string(),
-        push_subscriptions: Arc::new(RwLock::new(Vec::new())),
+        shutdown: state.shutdown.clone(),
     })
     .await;
 
     Ok(Json("ok"))
 }
 
-/// Push subscription handler
-#[allow(dead_code)]
-// `subscribe_push` local stub removed: routes now use `crate::push::subscribe_push`
-
-/// Push unsubscribe handler
-#[allow(dead_code)]
-// `unsubscribe_push` local stub removed: routes now use `crate::push::unsubscribe_push`
-
 /// Serve the AsyncAPI HTML documentation
 async fn serve_asyncapi_docs() -> Result<Html<String>, StatusCode> {
     let docs_path = std::path::Path::new("asyncapi-docs/index.html");
@@ -0,0 +1,358 @@
+//! Projection of the event log onto resources.
+//!
+//! Every resource in the `resources` table is derived from the CloudEvents in the event log.
+//! This module contains the pure logic that turns one event (plus the current state of the
+//! resource it touches) into a resource change, so the storage layer can apply it in the same
+//! transaction that appends the event.
+
+use serde_json::Value;
+
+use crate::schemas::{CloudEvent, JSONCommit};
+
+/// Change to the resources projection caused by a single event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceChange {
+    /// Create or replace the resource with the given data.
+    Upsert {
+        id: String,
+        resource_type: String,
+        data: Value,
+    },
+    /// Remove the resource.
+    Delete { id: String },
+}
+
+/// Whether the event carries a JSONCommit (accept both legacy and NL-VNG names).
+pub fn is_json_commit(event: &CloudEvent) -> bool {
+    event.event_type == "nl.vng.zaken.json-commit.v1" || event.event_type == "json.commit"
+}
+
+/// Id of the resource this event changes, if any.
+///
+/// JSONCommits target `resource_id`; other events with a subject are stored as a resource
+/// under their own event id. Fails when a JSONCommit event carries a malformed commit.
+pub fn resource_id(event: &CloudEvent) -> Result<Option<String>, serde_json::Error> {
+    let data = match &event.data {
+        Some(d) => d,
+        None => return Ok(None),
+    };
+
+    if is_json_commit(event) {
+        let commit: JSONCommit = serde_json::from_value(data.clone())?;
+        Ok(Some(commit.resource_id))
+    } else if event.subject.is_some() {
+        Ok(Some(event.id.clone()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Id of the issue a comment, task or other timeline item belongs to.
+///
+/// Timeline events carry the issue id as their subject; for the issue itself the subject is
+/// its own id, so there is no parent.
+pub fn parent_issue_id(event: &CloudEvent, resource_id: &str) -> Option<String> {
+    event
+        .subject
+        .as_deref()
+        .filter(|subject| *subject != resource_id)
+        .map(str::to_string)
+}
+
+/// Compute the change `event` makes to the resource it targets.
+///
+/// `existing` is the current state of the resource returned by [`resource_id`], or `None`
+/// if it does not exist yet. Returns `Ok(None)` for events that do not touch a resource.
+pub fn apply_event(
+    event: &CloudEvent,
+    existing: Option<Value>,
+) -> Result<Option<ResourceChange>, serde_json::Error> {
+    // Extract data from the event
+    let data = match &event.data {
+        Some(d) => d,
+        None => return Ok(None), // No data to process
+    };
+
+    if !is_json_commit(event) {
+        // For other event types we store the data as-is if a subject exists
+        return Ok(event
+            .subject
+            .as_deref()
+            .map(|subject| ResourceChange::Upsert {
+                id: event.id.clone(),
+                resource_type: extract_resource_type_from_subject(subject).to_string(),
+                data: data.clone(),
+            }));
+    }
+
+    let commit: JSONCommit = serde_json::from_value(data.clone())?;
+
+    // Handle deletion
+    if commit.deleted.unwrap_or(false) {
+        return Ok(Some(ResourceChange::Delete {
+            id: commit.resource_id,
+        }));
+    }
+
+    let resource_type = determine_resource_type(event, &commit);
+
+    // Apply changes (merge patch or replace with resource_data)
+    let new_resource = if let Some(mut existing) = existing {
+        // Apply patch if provided
+        if let Some(patch) = &commit.patch {
+            apply_json_merge_patch(&mut existing, patch);
+        }
+        // Override with full resource_data if provided
+        if let Some(resource_data) = &commit.resource_data {
+            existing = resource_data.clone();
+        }
+        existing
+    } else {
+        // New resource - use resource_data if available, else empty object
+        commit
+            .resource_data
+            .clone()
+            .unwrap_or_else(|| serde_json::json!({}))
+    };
+
+    Ok(Some(ResourceChange::Upsert {
+        id: commit.resource_id,
+        resource_type,
+        data: new_resource,
+    }))
+}
+
+/// Determine resource type more robustly:
+/// 1. Prefer schema hint (if it contains known type names)
+/// 2. Fallback to subject hint
+/// 3. Inspect resource_data keys (title/content/cta/moments/url)
+/// 4. Otherwise "unknown"
+fn determine_resource_type(event: &CloudEvent, commit: &JSONCommit) -> String {
+    let from_schema = extract_resource_type_from_schema(&commit.schema);
+    if from_schema != "unknown" {
+        return from_schema.to_string();
+    }
+
+    if let Some(subject) = &event.subject {
+        let subj_type = extract_resource_type_from_subject(subject);
+        if subj_type != "unknown" {
+            return subj_type.to_string();
+        }
+    }
+
+    if let Some(obj) = commit.resource_data.as_ref().and_then(|d| d.as_object()) {
+        if obj.contains_key("title") {
+            return "issue".to_string();
+        } else if obj.contains_key("content") {
+            return "comment".to_string();
+        } else if obj.contains_key("cta") {
+            return "task".to_string();
+        } else if obj.contains_key("moments") {
+            return "planning".to_string();
+        } else if obj.get("url").is_some() || obj.get("size").is_some() {
+            return "document".to_string();
+        }
+    }
+
+    "unknown".to_string()
+}
+
+/// Extract resource type from schema URL
+fn extract_resource_type_from_schema(schema: &str) -> &str {
+    if schema.contains("Issue") {
+        "issue"
+    } else if schema.contains("Comment") {
+        "comment"
+    } else if schema.contains("Task") {
+        "task"
+    } else if schema.contains("Planning") {
+        "planning"
+    } else if schema.contains("Document") {
+        "document"
+    } else {
+        "unknown"
+    }
+}
+
+/// Extract resource type from subject
+fn extract_resource_type_from_subject(subject: &str) -> &str {
+    if subject.contains("issue") {
+        "issue"
+    } else if subject.contains("comment") {
+        "comment"
+    } else if subject.contains("task") {
+        "task"
+    } else if subject.contains("planning") {
+        "planning"
+    } else if subject.contains("document") {
+        "document"
+    } else {
+        "unknown"
+    }
+}
+
+/// Apply JSON Merge Patch (RFC 7396)
+pub fn apply_json_merge_patch(target: &mut Value, patch: &Value) {
+    if !patch.is_object() {
+        *target = patch.clone();
+        return;
+    }
+
+    if !target.is_object() {
+        *target = serde_json::json!({});
+    }
+
+    let target_obj = target.as_object_mut().unwrap();
+    let patch_obj = patch.as_object().unwrap();
+
+    for (key, value) in patch_obj {
+        if value.is_null() {
+            target_obj.remove(key);
+        } else if value.is_object() && target_obj.contains_key(key) {
+            let mut target_value = target_obj.get(key).unwrap().clone();
+            apply_json_merge_patch(&mut target_value, value);
+            target_obj.insert(key.clone(), target_value);
+        } else {
+            target_obj.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_event(commit: Value) -> CloudEvent {
+        CloudEvent {
+            specversion: "1.0".to_string(),
+            id: "event-1".to_string(),
+            source: "test".to_string(),
+            subject: Some("1".to_string()),
+            event_type: "json.commit".to_string(),
+            time: None,
+            datacontenttype: Some("application/json".to_string()),
+            dataschema: None,
+            dataref: None,
+            sequence: None,
+            sequencetype: None,
+            data: Some(commit),
+        }
+    }
+
+    #[test]
+    fn test_apply_event_creates_patches_and_deletes() {
+        let create = commit_event(serde_json::json!({
+            "schema": "http://localhost:8000/schemas/Issue",
+            "resource_id": "1",
+            "resource_data": {"title": "Paspoort", "status": "open"}
+        }));
+        assert_eq!(resource_id(&create).unwrap().as_deref(), Some("1"));
+
+        let created = apply_event(&create, None).unwrap();
+        let data = match created {
+            Some(ResourceChange::Upsert {
+                resource_type,
+                data,
+                ..
+            }) => {
+                assert_eq!(resource_type, "issue");
+                data
+            }
+            other => panic!("unexpected change: {:?}", other),
+        };
+
+        let patch = commit_event(serde_json::json!({
+            "schema": "http://localhost:8000/schemas/Issue",
+            "resource_id": "1",
+            "patch": {"status": "closed"}
+        }));
+        match apply_event(&patch, Some(data)).unwrap() {
+            Some(ResourceChange::Upsert { data, .. }) => {
+                assert_eq!(data["title"], "Paspoort");
+                assert_eq!(data["status"], "closed");
+            }
+            other => panic!("unexpected change: {:?}", other),
+        }
+
+        let delete = commit_event(serde_json::json!({
+            "schema": "http://localhost:8000/schemas/Issue",
+            "resource_id": "1",
+            "deleted": true
+        }));
+        assert_eq!(
+            apply_event(&delete, None).unwrap(),
+            Some(ResourceChange::Delete {
+                id: "1".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parent_issue_id_uses_subject_of_timeline_items() {
+        let mut event = commit_event(serde_json::json!({}));
+        assert_eq!(parent_issue_id(&event, "1"), None);
+        assert_eq!(parent_issue_id(&event, "comment-1").as_deref(), Some("1"));
+
+        event.subject = None;
+        assert_eq!(parent_issue_id(&event, "comment-1"), None);
+    }
+
+    #[test]
+    fn test_apply_event_rejects_malformed_commit() {
+        let event = commit_event(serde_json::json!({"resource_id": 42}));
+        assert!(resource_id(&event).is_err());
+        assert!(apply_event(&event, None).is_err());
+    }
+
+    #[test]
+    fn test_apply_json_merge_patch() {
+        let mut target = serde_json::json!({
+            "title": "Old Title",
+            "status": "open",
+            "nested": {
+                "a": 1,
+                "b": 2
+            }
+        });
+
+        let patch = serde_json::json!({
+            "title": "New Title",
+            "status": null,
+            "nested": {
+                "b": 3,
+                "c": 4
+            }
+        });
+
+        apply_json_merge_patch(&mut target, &patch);
+
+        assert_eq!(target["title"], "New Title");
+        assert!(!target.as_object().unwrap().contains_key("status"));
+        assert_eq!(target["nested"]["a"], 1);
+        assert_eq!(target["nested"]["b"], 3);
+        assert_eq!(target["nested"]["c"], 4);
+    }
+
+    #[test]
+    fn test_extract_resource_type_from_schema() {
+        assert_eq!(
+            extract_resource_type_from_schema("http://example.com/Issue"),
+            "issue"
+        );
+        assert_eq!(
+            extract_resource_type_from_schema("http://example.com/Comment"),
+            "comment"
+        );
+        assert_eq!(
+            extract_resource_type_from_schema("http://example.com/Task"),
+            "task"
+        );
+    }
+
+    #[test]
+    fn test_extract_resource_type_from_subject() {
+        assert_eq!(extract_resource_type_from_subject("issue/123"), "issue");
+        assert_eq!(extract_resource_type_from_subject("comment/456"), "comment");
+        assert_eq!(extract_resource_type_from_subject("unknown/789"), "unknown");
+    }
+}
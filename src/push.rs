@@ -1,56 +1,884 @@
+//! Web Push: the subscribe/unsubscribe endpoints and notifying subscriptions about events.
+
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Json};
+use futures_util::stream::{self, StreamExt};
+use isahc::config::Configurable;
+use isahc::HttpClient;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use web_push::*;
+
+use crate::error::{Problem, StorageError};
+use crate::handlers::{ApiJson, AppState};
+use crate::projection;
+use crate::schemas::{CloudEvent, JSONCommit};
+use crate::storage::{CommittedState, Storage};
+use crate::types::{PushDeliveryStats, PushFilters, PushKeys, PushSubscription};
+use crate::validation;
+use crate::vapid::VapidKeys;
+
+/// Body of `POST /api/push/unsubscribe`. Browsers send the whole subscription; only the
+/// endpoint identifies it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsubscribeRequest {
+    pub endpoint: String,
+}
 
-/// Local copy of PushSubscription struct so this module compiles even if other modules
-/// haven't yet imported or re-exported it. Keeping a local definition here makes the
-/// push implementation self-contained. If a single canonical definition exists elsewhere
-/// (e.g., in `lib.rs`), you can remove this duplicate later.
-#[derive(Clone, Serialize, Deserialize)]
-pub struct PushSubscription {
+/// Body of `POST /api/push/subscribe`: the browser's `PushSubscription`, with optional
+/// filters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscribeRequest {
     pub endpoint: String,
-    #[serde(rename = "expirationTime")]
-    pub expiration_time: Option<String>,
+    #[serde(rename = "expirationTime", default)]
+    pub expiration_time: Option<f64>,
     pub keys: PushKeys,
+    /// Replace the subscription's filters; a known endpoint keeps its filters when absent
+    #[serde(default)]
+    pub filters: Option<PushFilters>,
 }
 
-/// Local copy of PushKeys used by PushSubscription.
-#[derive(Clone, Serialize, Deserialize)]
-pub struct PushKeys {
-    pub p256dh: String,
-    pub auth: String,
+impl From<PushSubscription> for SubscribeRequest {
+    fn from(subscription: PushSubscription) -> Self {
+        Self {
+            endpoint: subscription.endpoint,
+            expiration_time: subscription.expiration_time,
+            keys: subscription.keys,
+            filters: Some(subscription.filters),
+        }
+    }
 }
 
-use serde_json::json;
-use web_push::*;
+/// POST /api/push/subscribe - Store a browser's push subscription
+///
+/// Subscriptions are deduplicated by endpoint: subscribing again replaces the keys of the
+/// existing subscription, and its filters only when the body has `filters` (browsers renew
+/// subscriptions without them). Answers `201 Created` for a new endpoint and `200 OK`
+/// otherwise.
+pub async fn subscribe_push(
+    State(state): State<AppState>,
+    ApiJson(request): ApiJson<SubscribeRequest>,
+) -> Result<StatusCode, Problem> {
+    let replace_filters = request.filters.is_some();
+    let subscription = PushSubscription {
+        endpoint: request.endpoint,
+        expiration_time: request.expiration_time,
+        keys: request.keys,
+        filters: request.filters.unwrap_or_default(),
+    };
+    check_subscription(&subscription)?;
+    let created = if replace_filters {
+        state.storage.put_push_subscription(&subscription).await?
+    } else {
+        state.storage.renew_push_subscription(&subscription).await?
+    };
+    println!(
+        "[push] {} subscription {}",
+        if created { "added" } else { "updated" },
+        subscription.endpoint
+    );
+    Ok(if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    })
+}
+
+/// POST /api/push/unsubscribe - Remove the subscription for an endpoint
+///
+/// Answers `204 No Content` whether or not the endpoint was subscribed, so browsers can
+/// retry safely.
+pub async fn unsubscribe_push(
+    State(state): State<AppState>,
+    ApiJson(request): ApiJson<UnsubscribeRequest>,
+) -> Result<StatusCode, Problem> {
+    if state
+        .storage
+        .delete_push_subscription(&request.endpoint)
+        .await?
+    {
+        println!("[push] removed subscription {}", request.endpoint);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Public key browsers pass to `PushManager.subscribe` as `applicationServerKey`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VapidPublicKey {
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+}
+
+/// GET /api/push/vapid-public-key - The VAPID public key to subscribe with
+pub async fn vapid_public_key(State(state): State<AppState>) -> Json<VapidPublicKey> {
+    Json(VapidPublicKey {
+        public_key: state.push.vapid_public_key().to_string(),
+    })
+}
+
+/// Delivery statistics of one push subscription. The endpoint itself is left out: it is the
+/// capability to send to that browser.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionStats {
+    /// Stable, opaque id of the subscription, see [`subscription_id`]
+    pub id: String,
+    /// Host of the push service, e.g. `fcm.googleapis.com`
+    pub push_service: String,
+    #[serde(flatten)]
+    pub stats: PushDeliveryStats,
+}
+
+impl SubscriptionStats {
+    fn new(endpoint: &str, stats: PushDeliveryStats) -> Self {
+        let host = endpoint
+            .split_once("://")
+            .map_or(endpoint, |(_, rest)| rest);
+        Self {
+            id: subscription_id(endpoint),
+            push_service: host.split('/').next().unwrap_or_default().to_string(),
+            stats,
+        }
+    }
+}
+
+/// Opaque id of the subscription for `endpoint`: the first 8 bytes of its SHA-256, in hex.
+pub fn subscription_id(endpoint: &str) -> String {
+    Sha256::digest(endpoint.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// GET /admin/push-stats - Delivery statistics per subscription
+pub async fn push_delivery_stats(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<SubscriptionStats>>, Problem> {
+    let stats = state.storage.list_push_delivery_stats().await?;
+    Ok(Json(
+        stats
+            .into_iter()
+            .map(|(endpoint, stats)| SubscriptionStats::new(&endpoint, stats))
+            .collect(),
+    ))
+}
+
+/// Reject subscriptions that can never be delivered to.
+#[allow(clippy::result_large_err)] // the problem becomes the response body
+fn check_subscription(subscription: &PushSubscription) -> Result<(), Problem> {
+    let invalid = |field: &str, detail: &str| {
+        Err(
+            Problem::typed(StatusCode::UNPROCESSABLE_ENTITY, "invalid-body", detail)
+                .with_field(field),
+        )
+    };
+    if !subscription.endpoint.starts_with("https://") {
+        return invalid("endpoint", "endpoint must be an https URL");
+    }
+    if subscription.keys.p256dh.is_empty() {
+        return invalid("keys.p256dh", "keys.p256dh must not be empty");
+    }
+    if subscription.keys.auth.is_empty() {
+        return invalid("keys.auth", "keys.auth must not be empty");
+    }
+    let filters = &subscription.filters;
+    if filters.subjects.iter().any(String::is_empty) {
+        return invalid(
+            "filters.subjects",
+            "filters.subjects must not contain empty ids",
+        );
+    }
+    if filters.event_types.iter().any(String::is_empty) {
+        return invalid(
+            "filters.event_types",
+            "filters.event_types must not contain empty types",
+        );
+    }
+    if filters.assignee.as_deref() == Some("") {
+        return invalid("filters.assignee", "filters.assignee must not be empty");
+    }
+    if filters.mentioned.as_deref() == Some("") {
+        return invalid("filters.mentioned", "filters.mentioned must not be empty");
+    }
+    Ok(())
+}
+
+/// Events waiting for their notifications to be sent; when the queue is full, events are
+/// dropped (and logged) rather than slowing down `POST /events`.
+const PUSH_QUEUE_CAPACITY: usize = 1024;
+/// Notifications being sent at the same time, over all events. Deliveries waiting to retry
+/// do not count.
+const MAX_CONCURRENT_SENDS: usize = 8;
+/// Events whose notifications are being delivered at the same time. When slow push services
+/// hold up this many, the dispatcher stops taking events and the queue fills up instead.
+const MAX_EVENTS_IN_FLIGHT: usize = 32;
+/// Give up on a push service that does not answer in time (and retry later).
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longer comment texts are cut off in the notification body.
+const MAX_BODY_CHARS: usize = 140;
+
+/// Retries of notifications a push service could not take right now.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts per notification, including the first
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled for every next one
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts. When `Retry-After` asks for more, the notification is
+    /// given up rather than holding up the queue.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after `attempts` failed attempts, or `None` to give up.
+    /// `retry_after` is what the push service asked for.
+    pub fn backoff(&self, attempts: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let wait = retry_after.unwrap_or_else(|| {
+            let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+            self.initial_backoff
+                .saturating_mul(factor)
+                .min(self.max_backoff)
+        });
+        (wait <= self.max_backoff).then_some(wait)
+    }
+}
+
+/// Why a notification was not delivered.
+#[derive(Debug, thiserror::Error)]
+pub enum DeliveryError {
+    /// The push service no longer knows the subscription (404 or 410); it is removed.
+    #[error("subscription expired (push service answered {0})")]
+    Expired(u16),
+    /// The push service may accept the notification later: 429, 5xx or no answer.
+    #[error("{reason}")]
+    Transient {
+        reason: String,
+        retry_after: Option<Duration>,
+    },
+    /// Sending it again will not help.
+    #[error("{0}")]
+    Rejected(String),
+}
+
+/// Human-readable notification about a processed event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    /// What happened, e.g. `comment.created`; see [`describe_commit`]
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    /// Page the notification opens, e.g. `/zaak/42`
+    pub url: String,
+    /// Notifications with the same tag replace each other: one per zaak
+    pub tag: String,
+    pub event_id: String,
+    /// Who made the change; the service worker hides notifications about your own changes
+    pub actor: Option<String>,
+}
+
+impl Notification {
+    /// Payload the service worker (`sw.js`) shows.
+    fn payload(&self) -> Value {
+        json!({
+            "title": self.title,
+            "body": self.body,
+            "icon": "/icon-192.png",
+            "badge": "/icon-192.png",
+            "tag": self.tag,
+            "data": {
+                "url": self.url,
+                "eventId": self.event_id,
+                "actor": self.actor
+            }
+        })
+    }
+}
+
+/// Sends push notifications for committed events from a bounded background queue.
+///
+/// Cloning is cheap; all clones feed the same worker. The worker only holds a weak
+/// reference to storage; call [`PushDispatcher::shutdown`] before closing the database.
+#[derive(Clone)]
+pub struct PushDispatcher {
+    tx: mpsc::Sender<(CloudEvent, CommittedState)>,
+    vapid: Arc<VapidKeys>,
+    stop: watch::Sender<bool>,
+    worker: Arc<std::sync::Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl PushDispatcher {
+    /// Start the background worker that notifies the subscriptions in `storage`, signing
+    /// with `vapid`.
+    pub fn spawn(storage: &Arc<Storage>, vapid: VapidKeys) -> Self {
+        Self::spawn_with_retry_policy(storage, vapid, RetryPolicy::default())
+    }
+
+    /// Like [`PushDispatcher::spawn`], retrying transient failures as `policy` says.
+    pub fn spawn_with_retry_policy(
+        storage: &Arc<Storage>,
+        vapid: VapidKeys,
+        policy: RetryPolicy,
+    ) -> Self {
+        let client = match HttpClient::builder().timeout(SEND_TIMEOUT).build() {
+            Ok(client) => Some(client),
+            Err(e) => {
+                eprintln!(
+                    "[push] cannot create push client, notifications disabled: {}",
+                    e
+                );
+                None
+            }
+        };
+        Self::spawn_with_client(storage, client, vapid, policy)
+    }
+
+    /// Start the worker sending with `client`; without one, nothing is sent.
+    fn spawn_with_client(
+        storage: &Arc<Storage>,
+        client: Option<HttpClient>,
+        vapid: VapidKeys,
+        policy: RetryPolicy,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(PUSH_QUEUE_CAPACITY);
+        let (stop, stopped) = watch::channel(false);
+        let vapid = Arc::new(vapid);
+        let worker = tokio::spawn(run_dispatcher(
+            Arc::downgrade(storage),
+            client,
+            vapid.clone(),
+            policy,
+            rx,
+            stopped,
+        ));
+        Self {
+            tx,
+            vapid,
+            stop,
+            worker: Arc::new(std::sync::Mutex::new(Some(worker))),
+        }
+    }
+
+    /// The VAPID public key browsers must subscribe with.
+    pub fn vapid_public_key(&self) -> &str {
+        self.vapid.public_key()
+    }
+
+    /// Stop taking events and wait until the notifications already queued have been
+    /// delivered or given up on. The worker briefly holds the storage while it records
+    /// deliveries, so call this before closing it.
+    pub async fn shutdown(&self) {
+        self.stop.send_replace(true);
+        let worker = self.worker.lock().unwrap().take();
+        if let Some(worker) = worker {
+            if let Err(e) = worker.await {
+                eprintln!("[push] dispatcher failed: {}", e);
+            }
+        }
+    }
+
+    /// Queue notifications for an event that has been committed, described with the
+    /// resources as the event left them. Never waits.
+    pub fn dispatch(&self, event: CloudEvent, committed: CommittedState) {
+        if let Err(TrySendError::Full((event, _))) = self.tx.try_send((event, committed)) {
+            eprintln!("[push] queue full; no notifications for event {}", event.id);
+        }
+    }
+}
+
+async fn run_dispatcher(
+    storage: Weak<Storage>,
+    client: Option<HttpClient>,
+    vapid: Arc<VapidKeys>,
+    policy: RetryPolicy,
+    mut rx: mpsc::Receiver<(CloudEvent, CommittedState)>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let Some(client) = client else {
+        return;
+    };
+
+    let sends = Arc::new(Semaphore::new(MAX_CONCURRENT_SENDS));
+    let in_flight = Arc::new(Semaphore::new(MAX_EVENTS_IN_FLIGHT));
+    let mut deliveries = JoinSet::new();
+    let mut stopping = false;
+    loop {
+        while deliveries.try_join_next().is_some() {}
+        let permit = in_flight
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        // On shutdown, take what is queued already and nothing more
+        let next = tokio::select! {
+            next = rx.recv() => next,
+            _ = stop.wait_for(|stop| *stop), if !stopping => {
+                stopping = true;
+                rx.close();
+                continue;
+            }
+        };
+        let Some((event, committed)) = next else {
+            break;
+        };
+        let Some(strong) = storage.upgrade() else {
+            break;
+        };
+        let prepared = prepare_notification(&strong, &event, &committed).await;
+        drop(strong);
+
+        let (notification, subscriptions) = match prepared {
+            Ok(Some(prepared)) => prepared,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!(
+                    "[push] cannot prepare notification for event {}: {}",
+                    event.id, e
+                );
+                continue;
+            }
+        };
+
+        // Deliveries run on their own, so one waiting to retry does not hold up the queue
+        let (client, storage, vapid, sends) = (
+            client.clone(),
+            storage.clone(),
+            vapid.clone(),
+            sends.clone(),
+        );
+        deliveries.spawn(async move {
+            notify_subscriptions(
+                &client,
+                &storage,
+                &vapid,
+                &policy,
+                &sends,
+                &notification,
+                subscriptions,
+            )
+            .await;
+            drop(permit);
+        });
+    }
+    while deliveries.join_next().await.is_some() {}
+}
+
+/// Deliver `notification` to every subscription, removing the expired ones and recording
+/// how each delivery went. Every attempt waits for a permit from `sends`.
+async fn notify_subscriptions(
+    client: &HttpClient,
+    storage: &Weak<Storage>,
+    vapid: &VapidKeys,
+    policy: &RetryPolicy,
+    sends: &Semaphore,
+    notification: &Notification,
+    subscriptions: Vec<PushSubscription>,
+) {
+    stream::iter(subscriptions)
+        .for_each_concurrent(MAX_CONCURRENT_SENDS, |subscription| async move {
+            let (result, retries) =
+                deliver(client, vapid, policy, sends, &subscription, notification).await;
+            let Some(storage) = storage.upgrade() else {
+                return;
+            };
+            if let Err(e) = record_delivery(&storage, &subscription.endpoint, result, retries).await
+            {
+                eprintln!(
+                    "[push] cannot record delivery to {}: {}",
+                    subscription.endpoint, e
+                );
+            }
+        })
+        .await;
+}
+
+/// Send `notification`, retrying transient failures as `policy` allows. Returns the result
+/// of the last attempt and the number of retries.
+async fn deliver(
+    client: &HttpClient,
+    vapid: &VapidKeys,
+    policy: &RetryPolicy,
+    sends: &Semaphore,
+    subscription: &PushSubscription,
+    notification: &Notification,
+) -> (Result<(), DeliveryError>, u32) {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = {
+            let _permit = sends
+                .acquire()
+                .await
+                .expect("the semaphore is never closed");
+            send_push_notification(client, vapid, subscription, notification).await
+        };
+        let Err(DeliveryError::Transient { retry_after, .. }) = &result else {
+            return (result, attempts - 1);
+        };
+        let Some(wait) = policy.backoff(attempts, *retry_after) else {
+            return (result, attempts - 1);
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Remove an expired subscription, or add a delivery to its statistics.
+async fn record_delivery(
+    storage: &Storage,
+    endpoint: &str,
+    result: Result<(), DeliveryError>,
+    retries: u32,
+) -> Result<(), StorageError> {
+    match &result {
+        Ok(()) => {}
+        Err(DeliveryError::Expired(status)) => {
+            if storage.delete_push_subscription(endpoint).await? {
+                println!(
+                    "[push] removed expired subscription {} (push service answered {})",
+                    endpoint, status
+                );
+            }
+            return Ok(());
+        }
+        Err(e) => eprintln!(
+            "[push] failed to notify {} after {} retries: {}",
+            endpoint, retries, e
+        ),
+    }
+    let now = chrono::Utc::now();
+    storage
+        .update_push_delivery_stats(endpoint, |stats| {
+            stats.retries += u64::from(retries);
+            match result {
+                Ok(()) => {
+                    stats.delivered += 1;
+                    stats.last_delivered = Some(now);
+                }
+                Err(e) => {
+                    stats.failed += 1;
+                    stats.last_failed = Some(now);
+                    stats.last_error = Some(e.to_string());
+                }
+            }
+        })
+        .await?;
+    Ok(())
+}
+
+/// The notification for `event` and the subscriptions to send it to, or `None` when there
+/// is nothing to tell or nobody to tell it to. The event is described with the resources as
+/// it left them, not as they are by the time the queue gets to it.
+async fn prepare_notification(
+    storage: &Storage,
+    event: &CloudEvent,
+    committed: &CommittedState,
+) -> Result<Option<(Notification, Vec<PushSubscription>)>, StorageError> {
+    let Some(commit) = event
+        .data
+        .as_ref()
+        .filter(|_| projection::is_json_commit(event))
+        .and_then(|data| serde_json::from_value::<JSONCommit>(data.clone()).ok())
+    else {
+        return Ok(None);
+    };
+    let mut subscriptions = storage.list_push_subscriptions().await?;
+    if subscriptions.is_empty() {
+        return Ok(None);
+    }
+
+    let resource = committed.resource.as_ref();
+    let zaak = committed.subject.as_ref();
+    let Some(notification) = describe_commit(event, &commit, resource, zaak) else {
+        return Ok(None);
+    };
+
+    let zaak_id = zaak_id(event, &commit);
+    let topic = Topic::for_commit(&notification.kind, &commit, &zaak_id, resource, zaak);
+    subscriptions.retain(|subscription| topic.matches(&subscription.filters));
+    if subscriptions.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((notification, subscriptions)))
+}
+
+/// What subscription filters are matched against: a notification and the zaak it is about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topic<'a> {
+    /// The notification's kind, e.g. `comment.created`
+    pub kind: &'a str,
+    /// Id of the issue the event is about
+    pub zaak_id: &'a str,
+    /// Who the zaak is assigned to after the event
+    pub assignee: Option<&'a str>,
+    /// People mentioned by the comment the event creates or changes
+    pub mentions: Vec<&'a str>,
+}
+
+impl<'a> Topic<'a> {
+    /// The topic of a JSONCommit described as `kind`, given the resource after the commit and
+    /// the zaak.
+    pub fn for_commit(
+        kind: &'a str,
+        commit: &'a JSONCommit,
+        zaak_id: &'a str,
+        resource: Option<&'a Value>,
+        zaak: Option<&'a Value>,
+    ) -> Self {
+        let mentions = resource
+            .or(commit.resource_data.as_ref())
+            .and_then(|r| r.get("mentions"))
+            .and_then(Value::as_array)
+            .map(|mentions| mentions.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        Self {
+            kind,
+            zaak_id,
+            assignee: zaak.and_then(|z| z.get("assignee")).and_then(Value::as_str),
+            mentions,
+        }
+    }
+
+    /// Whether a subscription with these filters is notified about this topic.
+    pub fn matches(&self, filters: &PushFilters) -> bool {
+        if !filters.event_types.is_empty() && !filters.event_types.iter().any(|t| t == self.kind) {
+            return false;
+        }
+
+        let selects_zaken = !filters.subjects.is_empty()
+            || filters.assignee.is_some()
+            || filters.mentioned.is_some();
+        let follows = filters.subjects.iter().any(|s| s == self.zaak_id);
+        let assigned = filters
+            .assignee
+            .as_deref()
+            .zip(self.assignee)
+            .is_some_and(|(wanted, assignee)| wanted.eq_ignore_ascii_case(assignee));
+        let mentioned = filters.mentioned.as_deref().is_some_and(|email| {
+            self.mentions
+                .iter()
+                .any(|mention| is_mention_of(mention, email))
+        });
+        !selects_zaken || follows || assigned || mentioned
+    }
+}
+
+/// Whether a mention (`@alice@gemeente.nl`, `@alice` or `alice@gemeente.nl`) refers to the
+/// person with this email address.
+fn is_mention_of(mention: &str, email: &str) -> bool {
+    let mention = mention.trim_start_matches('@');
+    let local_part = email.split('@').next().unwrap_or(email);
+    mention.eq_ignore_ascii_case(email) || mention.eq_ignore_ascii_case(local_part)
+}
+
+/// Id of the issue an event is about: its `subject`, or the committed resource itself.
+fn zaak_id(event: &CloudEvent, commit: &JSONCommit) -> String {
+    event
+        .subject
+        .clone()
+        .unwrap_or_else(|| commit.resource_id.clone())
+}
+
+/// Describe a JSONCommit for people following the zaak: "new comment on zaak X", "status
+/// changed to closed", "task deadline set".
+///
+/// The notification's `kind`, which subscriptions filter on, is one of `zaak.created`,
+/// `zaak.deleted`, `zaak.status_changed`, `zaak.assigned`, `zaak.updated`, `comment.created`,
+/// `task.created`, `task.completed`, `task.deadline_set`, `task.deadline_removed`,
+/// `task.updated`, `planning.updated` and `document.created`.
+///
+/// `resource` is the resource after the commit and `zaak` the issue the event is about
+/// (`subject`), when they still exist. Commits nobody needs to hear about give `None`.
+pub fn describe_commit(
+    event: &CloudEvent,
+    commit: &JSONCommit,
+    resource: Option<&Value>,
+    zaak: Option<&Value>,
+) -> Option<Notification> {
+    let text = |value: Option<&Value>, key: &str| -> Option<String> {
+        value?.get(key)?.as_str().map(str::to_string)
+    };
+    // A value the commit sets, or else the current one
+    let field = |key: &str| {
+        text(commit.patch.as_ref(), key)
+            .or_else(|| text(commit.resource_data.as_ref(), key))
+            .or_else(|| text(resource, key))
+    };
+    let patched = |key: &str| commit.patch.as_ref().and_then(|p| p.get(key));
+
+    let schema = validation::schema_name(&commit.schema);
+    let zaak_id = zaak_id(event, commit);
+    let zaak_title = text(zaak, "title").unwrap_or_else(|| format!("zaak {}", zaak_id));
+    let created = commit.resource_data.is_some();
+
+    let (kind, title, body) = match schema {
+        "Issue" if commit.deleted == Some(true) => (
+            "zaak.deleted",
+            "Zaak verwijderd".to_string(),
+            format!("Zaak {} is verwijderd", zaak_id),
+        ),
+        _ if commit.deleted == Some(true) => return None,
+        "Issue" if created => (
+            "zaak.created",
+            format!("Nieuwe zaak: {}", zaak_title),
+            field("description").unwrap_or_default(),
+        ),
+        "Issue" => {
+            if let Some(status) = patched("status").and_then(Value::as_str) {
+                let mut body = format!("Nieuwe status: {}", status_label(status));
+                if let Some(resolution) = field("resolution") {
+                    body.push_str(&format!(" ({})", resolution));
+                }
+                (
+                    "zaak.status_changed",
+                    format!("Status gewijzigd: {}", zaak_title),
+                    body,
+                )
+            } else if let Some(assignee) = patched("assignee") {
+                let body = match assignee.as_str() {
+                    Some(assignee) => format!("Toegewezen aan {}", assignee),
+                    None => "Niet meer toegewezen".to_string(),
+                };
+                (
+                    "zaak.assigned",
+                    format!("Behandelaar gewijzigd: {}", zaak_title),
+                    body,
+                )
+            } else {
+                let changed: Vec<&str> = commit
+                    .patch
+                    .as_ref()
+                    .and_then(Value::as_object)
+                    .map(|patch| patch.keys().map(String::as_str).collect())
+                    .unwrap_or_default();
+                (
+                    "zaak.updated",
+                    format!("Zaak bijgewerkt: {}", zaak_title),
+                    format!("Gewijzigd: {}", changed.join(", ")),
+                )
+            }
+        }
+        "Comment" if created => {
+            let content = truncate(&field("content").unwrap_or_default());
+            let body = match &commit.actor {
+                Some(actor) => format!("{}: {}", actor, content),
+                None => content,
+            };
+            (
+                "comment.created",
+                format!("Nieuwe reactie op {}", zaak_title),
+                body,
+            )
+        }
+        "Task" => {
+            let cta = field("cta").unwrap_or_else(|| "taak".to_string());
+            if created {
+                let mut body = cta;
+                if let Some(deadline) = field("deadline") {
+                    body.push_str(&format!(" (uiterlijk {})", deadline));
+                }
+                (
+                    "task.created",
+                    format!("Nieuwe taak bij {}", zaak_title),
+                    body,
+                )
+            } else if patched("completed") == Some(&Value::Bool(true)) {
+                (
+                    "task.completed",
+                    format!("Taak afgerond: {}", cta),
+                    zaak_title,
+                )
+            } else if let Some(deadline) = patched("deadline") {
+                match deadline.as_str() {
+                    Some(deadline) => (
+                        "task.deadline_set",
+                        format!("Deadline gezet: {}", cta),
+                        format!("Uiterlijk {} - {}", deadline, zaak_title),
+                    ),
+                    None => (
+                        "task.deadline_removed",
+                        format!("Deadline verwijderd: {}", cta),
+                        zaak_title,
+                    ),
+                }
+            } else {
+                (
+                    "task.updated",
+                    format!("Taak bijgewerkt: {}", cta),
+                    zaak_title,
+                )
+            }
+        }
+        "Planning" => (
+            "planning.updated",
+            format!("Planning bijgewerkt voor {}", zaak_title),
+            field("title").unwrap_or_default(),
+        ),
+        "Document" if created => (
+            "document.created",
+            format!("Nieuw document bij {}", zaak_title),
+            field("title").unwrap_or_default(),
+        ),
+        _ => return None,
+    };
+
+    Some(Notification {
+        kind: kind.to_string(),
+        title,
+        body,
+        url: format!("/zaak/{}", zaak_id),
+        tag: format!("zaak-{}", zaak_id),
+        event_id: event.id.clone(),
+        actor: commit.actor.clone(),
+    })
+}
+
+/// Dutch label of an issue status.
+fn status_label(status: &str) -> &str {
+    match status {
+        "open" => "open",
+        "in_progress" => "in behandeling",
+        "closed" => "gesloten",
+        other => other,
+    }
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_BODY_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(MAX_BODY_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
 
 /// Send a push notification to a subscription
 ///
-/// This function constructs a Web Push payload and sends it using VAPID.
-/// In production you should load the VAPID private key from a secure secret store,
-/// not hardcode it.
+/// This function encrypts the notification payload for the subscription and signs the
+/// message with the server's VAPID key. The push service's answer is classified so the
+/// dispatcher knows whether to retry or to drop the subscription.
 pub async fn send_push_notification(
+    client: &HttpClient,
+    vapid: &VapidKeys,
     subscription: &PushSubscription,
-    title: &str,
-    body: &str,
-    url: &str,
-    event_id: &str,
-    event_actor: Option<&str>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // VAPID private key - in production, load from environment variable or secret store
-    // This is just an example key placeholder; replace it with a real key.
-    let vapid_private_key = "TyRumaZoZxriruLdV6XyHV8ZzcDb9yHqpV7pQsgBHDM";
-
-    // Build notification payload
-    let payload = json!({
-        "title": title,
-        "body": body,
-        "icon": "/icon-192.png",
-        "badge": "/icon-192.png",
-        "data": {
-            "url": url,
-            "eventId": event_id,
-            "actor": event_actor
-        }
-    });
+    notification: &Notification,
+) -> Result<(), DeliveryError> {
+    let rejected = |e: WebPushError| DeliveryError::Rejected(e.to_string());
 
     // Build subscription info for web-push
     let subscription_info = SubscriptionInfo::new(
@@ -60,25 +888,714 @@ pub async fn send_push_notification(
     );
 
     // Build the message
-    let mut builder = WebPushMessageBuilder::new(&subscription_info)?;
-    let payload_json = payload.to_string();
+    let mut builder = WebPushMessageBuilder::new(&subscription_info).map_err(rejected)?;
+    let payload_json = notification.payload().to_string();
     builder.set_payload(ContentEncoding::Aes128Gcm, payload_json.as_bytes());
+    builder.set_vapid_signature(vapid.sign(&subscription_info).map_err(rejected)?);
+    let message = builder.build().map_err(rejected)?;
+
+    let request = request_builder::build_request::<isahc::AsyncBody>(message);
+    let response = client
+        .send_async(request)
+        .await
+        .map_err(|e| DeliveryError::Transient {
+            reason: e.to_string(),
+            retry_after: None,
+        })?;
+    let status = response.status();
+    match status.as_u16() {
+        200..=299 => Ok(()),
+        code @ (404 | 410) => Err(DeliveryError::Expired(code)),
+        429 | 500..=599 => Err(DeliveryError::Transient {
+            reason: format!("push service answered {}", status),
+            retry_after: response
+                .headers()
+                .get("retry-after")
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after),
+        }),
+        _ => Err(DeliveryError::Rejected(format!(
+            "push service answered {}",
+            status
+        ))),
+    }
+}
 
-    // Add VAPID signature (using base64 private key)
-    let sig_builder =
-        VapidSignatureBuilder::from_base64(vapid_private_key, URL_SAFE_NO_PAD, &subscription_info)?;
-    builder.set_vapid_signature(sig_builder.build()?);
+/// A `Retry-After` header: a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::broadcast;
 
-    // Send the notification
-    let client = WebPushClient::new()?;
-    match client.send(builder.build()?).await {
-        Ok(_) => {
-            println!("📤 Push notification sent successfully");
-            Ok(())
+    fn subscription(endpoint: &str, auth: &str) -> PushSubscription {
+        PushSubscription {
+            endpoint: endpoint.to_string(),
+            expiration_time: None,
+            keys: PushKeys {
+                p256dh: "BNcRdreALRFXTkOOUHK1EtK2wtaz5Ry4YfYCA_0QTpQtUbVlUls0VJXg7A8u-Ts1XbjhazAkj7I99e8QcYP7DkM".to_string(),
+                auth: auth.to_string(),
+            },
+            filters: PushFilters::default(),
         }
-        Err(e) => {
-            eprintln!("❌ Failed to send push notification: {}", e);
-            Err(Box::new(e))
+    }
+
+    fn commit(id: &str, subject: &str, data: Value) -> CloudEvent {
+        CloudEvent {
+            specversion: "1.0".to_string(),
+            id: id.to_string(),
+            source: "test".to_string(),
+            subject: Some(subject.to_string()),
+            event_type: "json.commit".to_string(),
+            time: None,
+            datacontenttype: Some("application/json".to_string()),
+            dataschema: None,
+            dataref: None,
+            sequence: None,
+            sequencetype: None,
+            data: Some(data),
         }
     }
+
+    fn describe(event: &CloudEvent, resource: Option<&Value>) -> Option<(String, String)> {
+        let commit: JSONCommit = serde_json::from_value(event.data.clone().unwrap()).unwrap();
+        let zaak = json!({"title": "Kapvergunning Dorpsstraat 12", "status": "open"});
+        describe_commit(event, &commit, resource, Some(&zaak)).map(|n| (n.title, n.body))
+    }
+
+    #[test]
+    fn test_describe_commit() {
+        let comment = commit(
+            "e1",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Comment",
+                "resource_id": "comment-1",
+                "actor": "alice@gemeente.nl",
+                "resource_data": {"content": "Documenten zijn goedgekeurd"}
+            }),
+        );
+        assert_eq!(
+            describe(&comment, None),
+            Some((
+                "Nieuwe reactie op Kapvergunning Dorpsstraat 12".to_string(),
+                "alice@gemeente.nl: Documenten zijn goedgekeurd".to_string()
+            ))
+        );
+        let parsed: JSONCommit = serde_json::from_value(comment.data.clone().unwrap()).unwrap();
+        let notification = describe_commit(&comment, &parsed, None, None).unwrap();
+        assert_eq!(notification.kind, "comment.created");
+        assert_eq!(notification.title, "Nieuwe reactie op zaak 7");
+        assert_eq!(notification.url, "/zaak/7");
+        assert_eq!(notification.actor.as_deref(), Some("alice@gemeente.nl"));
+
+        let status = commit(
+            "e2",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "7",
+                "patch": {"status": "closed", "resolution": "toegekend"}
+            }),
+        );
+        assert_eq!(
+            describe(&status, None),
+            Some((
+                "Status gewijzigd: Kapvergunning Dorpsstraat 12".to_string(),
+                "Nieuwe status: gesloten (toegekend)".to_string()
+            ))
+        );
+
+        // The task is named after its current state, since the patch only has the deadline
+        let deadline = commit(
+            "e3",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Task",
+                "resource_id": "task-1",
+                "patch": {"deadline": "2024-03-15"}
+            }),
+        );
+        let task = json!({"cta": "Documenten controleren", "completed": false});
+        assert_eq!(
+            describe(&deadline, Some(&task)),
+            Some((
+                "Deadline gezet: Documenten controleren".to_string(),
+                "Uiterlijk 2024-03-15 - Kapvergunning Dorpsstraat 12".to_string()
+            ))
+        );
+
+        let deleted_comment = commit(
+            "e4",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Comment",
+                "resource_id": "comment-1",
+                "deleted": true
+            }),
+        );
+        assert_eq!(describe(&deleted_comment, None), None);
+        assert!(truncate(&"a".repeat(500)).chars().count() <= MAX_BODY_CHARS);
+    }
+
+    #[test]
+    fn test_topic_matches_filters() {
+        let topic = Topic {
+            kind: "comment.created",
+            zaak_id: "7",
+            assignee: Some("Alice@gemeente.nl"),
+            mentions: vec!["@bob"],
+        };
+        let filters = |f: serde_json::Value| -> PushFilters { serde_json::from_value(f).unwrap() };
+
+        assert!(topic.matches(&PushFilters::default()));
+        assert!(topic.matches(&filters(json!({"subjects": ["3", "7"]}))));
+        assert!(!topic.matches(&filters(json!({"subjects": ["3"]}))));
+        assert!(topic.matches(&filters(json!({"assignee": "alice@gemeente.nl"}))));
+        assert!(!topic.matches(&filters(json!({"assignee": "bob@gemeente.nl"}))));
+        assert!(topic.matches(&filters(json!({"mentioned": "bob@gemeente.nl"}))));
+        // Zaak filters are alternatives: following zaak 3 or being mentioned is enough
+        assert!(topic.matches(&filters(
+            json!({"subjects": ["3"], "mentioned": "bob@gemeente.nl"})
+        )));
+        // Notification kinds restrict on top of them
+        assert!(!topic.matches(&filters(
+            json!({"subjects": ["7"], "event_types": ["zaak.status_changed"]})
+        )));
+        assert!(topic.matches(&filters(
+            json!({"event_types": ["zaak.status_changed", "comment.created"]})
+        )));
+        // Every commit is a `json.commit` event; that says nothing about what happened
+        assert!(!topic.matches(&filters(json!({"event_types": ["json.commit"]}))));
+    }
+
+    #[tokio::test]
+    async fn test_prepare_notification_looks_up_zaak_and_subscriptions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        let create = commit(
+            "create",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "7",
+                "resource_data": {"title": "Paspoort aanvragen", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+        let comment = commit(
+            "comment",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Comment",
+                "resource_id": "comment-1",
+                "resource_data": {"content": "Foto ontvangen"}
+            }),
+        );
+        let (_, committed) = storage.store_event_committed(&comment).await.unwrap();
+
+        // Nobody to notify
+        assert!(prepare_notification(&storage, &comment, &committed)
+            .await
+            .unwrap()
+            .is_none());
+
+        storage
+            .put_push_subscription(&subscription("https://push.example.com/a", "a"))
+            .await
+            .unwrap();
+        let (notification, subscriptions) = prepare_notification(&storage, &comment, &committed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(notification.title, "Nieuwe reactie op Paspoort aanvragen");
+        assert_eq!(notification.body, "Foto ontvangen");
+        assert_eq!(notification.event_id, "comment");
+        assert_eq!(subscriptions.len(), 1);
+
+        // Renaming the zaak before the queue gets to the comment does not change what it says
+        let rename = commit(
+            "rename",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "7",
+                "patch": {"title": "Rijbewijs verlengen"}
+            }),
+        );
+        storage.store_event(&rename).await.unwrap();
+        let (notification, _) = prepare_notification(&storage, &comment, &committed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(notification.title, "Nieuwe reactie op Paspoort aanvragen");
+
+        // Only subscriptions whose filters match the zaak are notified
+        let mut other_zaak = subscription("https://push.example.com/b", "b");
+        other_zaak.filters.subjects = vec!["8".to_string()];
+        storage.put_push_subscription(&other_zaak).await.unwrap();
+        let mut assigned = subscription("https://push.example.com/c", "c");
+        assigned.filters.assignee = Some("alice@gemeente.nl".to_string());
+        storage.put_push_subscription(&assigned).await.unwrap();
+        let assign = commit(
+            "assign",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "7",
+                "patch": {"assignee": "alice@gemeente.nl"}
+            }),
+        );
+        let (_, committed) = storage.store_event_committed(&assign).await.unwrap();
+        let (_, subscriptions) = prepare_notification(&storage, &assign, &committed)
+            .await
+            .unwrap()
+            .unwrap();
+        let endpoints: Vec<&str> = subscriptions.iter().map(|s| s.endpoint.as_str()).collect();
+        assert_eq!(
+            endpoints,
+            ["https://push.example.com/a", "https://push.example.com/c"]
+        );
+    }
+
+    #[test]
+    fn test_retry_policy_backs_off_and_honors_retry_after() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1, None), Some(Duration::from_secs(1)));
+        assert_eq!(policy.backoff(2, None), Some(Duration::from_secs(2)));
+        assert_eq!(policy.backoff(3, None), Some(Duration::from_secs(4)));
+        assert_eq!(policy.backoff(4, None), None);
+        assert_eq!(
+            policy.backoff(1, Some(Duration::from_secs(30))),
+            Some(Duration::from_secs(30))
+        );
+        // Waiting longer than max_backoff would hold up the queue
+        assert_eq!(policy.backoff(1, Some(Duration::from_secs(3600))), None);
+
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    /// A push service that answers by the last path segment of the endpoint and counts the
+    /// requests per endpoint. It never answers `stall`.
+    async fn mock_push_service() -> (String, Arc<std::sync::Mutex<HashMap<String, u32>>>) {
+        use axum::extract::Path;
+        use axum::response::IntoResponse;
+
+        let hits = Arc::new(std::sync::Mutex::new(HashMap::<String, u32>::new()));
+        let counter = hits.clone();
+        let app = axum::Router::new().route(
+            "/push/{name}",
+            axum::routing::post(move |Path(name): Path<String>| async move {
+                let hit = {
+                    let mut hits = counter.lock().unwrap();
+                    let hit = hits.entry(name.clone()).or_default();
+                    *hit += 1;
+                    *hit
+                };
+                if name == "stall" {
+                    std::future::pending::<()>().await;
+                }
+                match (name.as_str(), hit) {
+                    ("ok", _) | ("flaky", 3) => StatusCode::CREATED.into_response(),
+                    ("gone", _) => StatusCode::GONE.into_response(),
+                    ("flaky", 1) => {
+                        (StatusCode::TOO_MANY_REQUESTS, [("retry-after", "0")]).into_response()
+                    }
+                    ("busy", _) => {
+                        (StatusCode::TOO_MANY_REQUESTS, [("retry-after", "3600")]).into_response()
+                    }
+                    ("bad", _) => StatusCode::BAD_REQUEST.into_response(),
+                    _ => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/push", address), hits)
+    }
+
+    #[tokio::test]
+    async fn test_delivery_prunes_expired_and_retries_transient_failures() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (base, hits) = mock_push_service().await;
+        let names = ["ok", "gone", "flaky", "down", "busy", "bad"];
+        let subscriptions: Vec<PushSubscription> = names
+            .iter()
+            .map(|name| subscription(&format!("{}/{}", base, name), "tBHItJI5svbpez7KI4CCXg"))
+            .collect();
+        for subscription in &subscriptions {
+            storage.put_push_subscription(subscription).await.unwrap();
+        }
+
+        let client = HttpClient::builder().proxy(None).build().unwrap();
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_secs(1),
+        };
+        let notification = Notification {
+            kind: "zaak.created".to_string(),
+            title: "Nieuwe zaak".to_string(),
+            body: "Paspoort aanvragen".to_string(),
+            url: "/zaak/7".to_string(),
+            tag: "zaak-7".to_string(),
+            event_id: "e1".to_string(),
+            actor: None,
+        };
+        notify_subscriptions(
+            &client,
+            &Arc::downgrade(&storage),
+            &VapidKeys::generate("mailto:beheer@gemeente.nl"),
+            &policy,
+            &Semaphore::new(MAX_CONCURRENT_SENDS),
+            &notification,
+            subscriptions,
+        )
+        .await;
+
+        let hits = hits.lock().unwrap().clone();
+        let expected_hits = [
+            ("ok", 1),
+            ("gone", 1),
+            ("flaky", 3),
+            ("down", 3),
+            ("busy", 1),
+            ("bad", 1),
+        ];
+        for (name, count) in expected_hits {
+            assert_eq!(hits[name], count, "requests to {}", name);
+        }
+
+        // The expired subscription is gone; the others have their statistics
+        let stats: HashMap<String, PushDeliveryStats> = storage
+            .list_push_delivery_stats()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(endpoint, stats)| (endpoint.rsplit('/').next().unwrap().to_string(), stats))
+            .collect();
+        assert!(!stats.contains_key("gone"));
+        assert_eq!(stats.len(), 5);
+        assert_eq!((stats["ok"].delivered, stats["ok"].retries), (1, 0));
+        assert!(stats["ok"].last_delivered.is_some());
+        assert_eq!((stats["flaky"].delivered, stats["flaky"].retries), (1, 2));
+        assert_eq!((stats["down"].failed, stats["down"].retries), (1, 2));
+        assert_eq!(
+            stats["down"].last_error.as_deref(),
+            Some("push service answered 503 Service Unavailable")
+        );
+        assert_eq!((stats["busy"].failed, stats["busy"].retries), (1, 0));
+        assert_eq!((stats["bad"].failed, stats["bad"].retries), (1, 0));
+        assert!(stats["bad"].last_failed.is_some());
+
+        // The statistics do not give the endpoints away
+        let Json(listed) = push_delivery_stats(State(AppState::new(
+            storage.clone(),
+            broadcast::channel(16).0,
+        )))
+        .await
+        .unwrap();
+        assert_eq!(listed.len(), 5);
+        let ok = format!("{}/ok", base);
+        let ok = listed
+            .iter()
+            .find(|s| s.id == subscription_id(&ok))
+            .unwrap();
+        assert_eq!(ok.id.len(), 16);
+        let host = base.trim_start_matches("http://").trim_end_matches("/push");
+        assert_eq!(ok.push_service, host);
+        assert_eq!(ok.stats.delivered, 1);
+        let json = serde_json::to_string(&listed).unwrap();
+        assert!(!json.contains("/ok"), "{}", json);
+    }
+
+    #[tokio::test]
+    async fn test_retries_wait_without_holding_up_other_deliveries() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (base, hits) = mock_push_service().await;
+        let subscriptions: Vec<PushSubscription> = ["down", "ok"]
+            .iter()
+            .map(|name| subscription(&format!("{}/{}", base, name), "tBHItJI5svbpez7KI4CCXg"))
+            .collect();
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(2),
+        };
+        let notification = Notification {
+            kind: "zaak.created".to_string(),
+            title: "Nieuwe zaak".to_string(),
+            body: "Paspoort aanvragen".to_string(),
+            url: "/zaak/7".to_string(),
+            tag: "zaak-7".to_string(),
+            event_id: "e1".to_string(),
+            actor: None,
+        };
+
+        // A single send at a time: "ok" only goes out if "down" waits for its retry without
+        // keeping the permit
+        let sending = tokio::spawn(async move {
+            notify_subscriptions(
+                &HttpClient::builder().proxy(None).build().unwrap(),
+                &Arc::downgrade(&storage),
+                &VapidKeys::generate("mailto:beheer@gemeente.nl"),
+                &policy,
+                &Semaphore::new(1),
+                &notification,
+                subscriptions,
+            )
+            .await;
+        });
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while hits.lock().unwrap().get("ok").is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("ok was held up by the retry of down");
+        sending.abort();
+    }
+
+    #[tokio::test]
+    async fn test_stalled_push_service_stops_the_dispatcher_taking_events() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (base, hits) = mock_push_service().await;
+        let create = commit(
+            "create",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "7",
+                "resource_data": {"title": "Paspoort aanvragen", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+        storage
+            .put_push_subscription(&subscription(
+                &format!("{}/stall", base),
+                "tBHItJI5svbpez7KI4CCXg",
+            ))
+            .await
+            .unwrap();
+
+        let dispatcher = PushDispatcher::spawn_with_client(
+            &storage,
+            Some(HttpClient::builder().proxy(None).build().unwrap()),
+            VapidKeys::generate("mailto:beheer@gemeente.nl"),
+            RetryPolicy::default(),
+        );
+        let queued = 3;
+        for n in 0..MAX_EVENTS_IN_FLIGHT + queued {
+            let comment = commit(
+                &format!("comment-{}", n),
+                "7",
+                json!({
+                    "schema": "http://localhost:8000/schemas/Comment",
+                    "resource_id": format!("comment-{}", n),
+                    "resource_data": {"content": "Foto ontvangen"}
+                }),
+            );
+            let (_, committed) = storage.store_event_committed(&comment).await.unwrap();
+            dispatcher.dispatch(comment, committed);
+        }
+
+        // The first events take every send, the others wait for one; the rest stays queued
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while hits.lock().unwrap().get("stall") != Some(&(MAX_CONCURRENT_SENDS as u32)) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the stalled sends did not start");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            dispatcher.tx.capacity(),
+            PUSH_QUEUE_CAPACITY - queued,
+            "the dispatcher kept taking events"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_deliveries_and_lets_go_of_storage() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let (base, hits) = mock_push_service().await;
+        let create = commit(
+            "create",
+            "7",
+            json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "7",
+                "resource_data": {"title": "Paspoort aanvragen", "status": "open"}
+            }),
+        );
+        let (_, committed) = storage.store_event_committed(&create).await.unwrap();
+        storage
+            .put_push_subscription(&subscription(
+                &format!("{}/ok", base),
+                "tBHItJI5svbpez7KI4CCXg",
+            ))
+            .await
+            .unwrap();
+
+        let dispatcher = PushDispatcher::spawn_with_client(
+            &storage,
+            Some(HttpClient::builder().proxy(None).build().unwrap()),
+            VapidKeys::generate("mailto:beheer@gemeente.nl"),
+            RetryPolicy::default(),
+        );
+        dispatcher.dispatch(create, committed);
+        dispatcher.shutdown().await;
+
+        // The queued notification went out and was recorded; storage can be closed
+        assert_eq!(hits.lock().unwrap().get("ok"), Some(&1));
+        let storage = Arc::try_unwrap(storage).unwrap_or_else(|_| panic!("storage still shared"));
+        let stats = storage.list_push_delivery_stats().await.unwrap();
+        assert_eq!(stats[0].1.delivered, 1);
+        storage.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_are_deduplicated_and_persisted() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let state = AppState::new(storage.clone(), broadcast::channel(16).0);
+        let endpoint = "https://push.example.com/send/abc";
+
+        let status = subscribe_push(
+            State(state.clone()),
+            ApiJson(subscription(endpoint, "one").into()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        // Subscribing again replaces the keys instead of adding a second subscription
+        let status = subscribe_push(
+            State(state.clone()),
+            ApiJson(subscription(endpoint, "two").into()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let status = subscribe_push(
+            State(state.clone()),
+            ApiJson(subscription("https://push.example.com/send/def", "three").into()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let problem = subscribe_push(
+            State(state.clone()),
+            ApiJson(subscription("ftp://x", "a").into()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.field.as_deref(), Some("endpoint"));
+
+        // Subscriptions survive a restart
+        drop(state);
+        Arc::try_unwrap(storage)
+            .ok()
+            .unwrap()
+            .close()
+            .await
+            .unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let subscriptions = storage.list_push_subscriptions().await.unwrap();
+        assert_eq!(subscriptions.len(), 2);
+        assert_eq!(subscriptions[0].keys.auth, "two");
+
+        let state = AppState::new(storage.clone(), broadcast::channel(16).0);
+        let request = UnsubscribeRequest {
+            endpoint: endpoint.to_string(),
+        };
+        for _ in 0..2 {
+            let status = unsubscribe_push(State(state.clone()), ApiJson(request.clone()))
+                .await
+                .unwrap();
+            assert_eq!(status, StatusCode::NO_CONTENT);
+        }
+        assert_eq!(storage.list_push_subscriptions().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resubscribing_keeps_filters_unless_given() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).await.unwrap());
+        let state = AppState::new(storage.clone(), broadcast::channel(16).0);
+        let endpoint = "https://push.example.com/send/abc";
+        let filters =
+            |f: Value| -> Option<PushFilters> { Some(serde_json::from_value(f).unwrap()) };
+        let request = |auth: &str, filters: Option<PushFilters>| SubscribeRequest {
+            filters,
+            ..subscription(endpoint, auth).into()
+        };
+        let stored = || async { storage.list_push_subscriptions().await.unwrap().remove(0) };
+
+        let following = filters(json!({"subjects": ["7"]}));
+        subscribe_push(
+            State(state.clone()),
+            ApiJson(request("one", following.clone())),
+        )
+        .await
+        .unwrap();
+
+        // Browsers renew their subscription with only the endpoint and keys
+        let renewal: SubscribeRequest = serde_json::from_value(json!({
+            "endpoint": endpoint,
+            "keys": {"p256dh": "BNcRdreALRFX", "auth": "two"}
+        }))
+        .unwrap();
+        assert_eq!(renewal.filters, None);
+        let status = subscribe_push(State(state.clone()), ApiJson(renewal))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let subscription = stored().await;
+        assert_eq!(subscription.keys.auth, "two");
+        assert_eq!(Some(subscription.filters), following);
+
+        // Filters in the body replace the stored ones, also with none at all
+        let assigned = filters(json!({"assignee": "alice@gemeente.nl"}));
+        subscribe_push(
+            State(state.clone()),
+            ApiJson(request("three", assigned.clone())),
+        )
+        .await
+        .unwrap();
+        assert_eq!(Some(stored().await.filters), assigned);
+        subscribe_push(
+            State(state.clone()),
+            ApiJson(request("four", filters(json!({})))),
+        )
+        .await
+        .unwrap();
+        assert!(stored().await.filters.is_empty());
+    }
 }
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 
+use crate::error::Problem;
+
 /// CloudEvents specification struct
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CloudEvent {
@@ -218,7 +220,7 @@ fn resolve_schema_refs(mut schema: Value, all_schemas: &HashMap<String, Value>)
                 // Handle allOf with $ref patterns
                 if let Some(Value::Array(all_of_array)) = map.get_mut("allOf") {
                     if all_of_array.len() == 1 {
-                        if let Some(Value::Object(ref_obj)) = all_of_array.get(0) {
+                        if let Some(Value::Object(ref_obj)) = all_of_array.first() {
                             if let Some(ref_value) = ref_obj.get("$ref") {
                                 if let Some(ref_str) = ref_value.as_str() {
                                     if let Some(definition_name) =
@@ -328,10 +330,17 @@ pub async fn handle_get_schemas_index() -> Json<Value> {
 }
 
 /// Get a specific schema by name
-pub async fn handle_get_schema(Path(name): Path<String>) -> Result<Json<Value>, StatusCode> {
+pub async fn handle_get_schema(Path(name): Path<String>) -> Result<Json<Value>, Problem> {
     match get_schema(&name) {
         Some(schema) => Ok(Json(schema)),
-        None => Err(StatusCode::NOT_FOUND),
+        None => Err(Problem::typed(
+            StatusCode::NOT_FOUND,
+            "not-found",
+            format!(
+                "schema {} does not exist; see /schemas for the available names",
+                name
+            ),
+        )),
     }
 }
 
@@ -367,7 +376,7 @@ mod tests {
         assert!(index.get("description").is_some());
 
         let schemas = index.get("schemas").unwrap().as_array().unwrap();
-        assert!(schemas.len() > 0);
+        assert!(!schemas.is_empty());
 
         // Check that key schemas are present
         let schema_names: Vec<String> = schemas
@@ -578,6 +587,7 @@ async fn test_get_nonexistent_schema_endpoint() {
     let result = handle_get_schema(path).await;
 
     assert!(result.is_err());
-    let status = result.unwrap_err();
-    assert_eq!(status, StatusCode::NOT_FOUND);
+    let problem = result.unwrap_err();
+    assert_eq!(problem.status, StatusCode::NOT_FOUND.as_u16());
+    assert_eq!(problem.problem_type, "/problems/not-found");
 }
@@ -4,21 +4,37 @@
 //! Dead helpers and per-document commits were removed in favor of background indexing
 //! with periodic commits to improve throughput and startup performance.
 
-use redb::{Database, ReadableTable, TableDefinition};
-use serde::{Deserialize, Serialize};
+use redb::{
+    Database, MultimapTableDefinition, ReadableTable, ReadableTableMetadata, TableDefinition,
+    WriteTransaction,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
 use std::path::Path;
 use std::sync::Arc;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::collector::{Count, DocSetCollector, FacetCollector, TopDocs};
+use tantivy::query::{
+    AllQuery, BooleanQuery, FuzzyTermQuery, Occur, Query, QueryParser, RangeQuery, TermQuery,
+};
 // Import Tantivy's `Value` trait under an alias so it does not conflict with serde_json::Value.
 // The alias brings the trait into scope for `as_str()` calls on Tantivy document values.
 use tantivy::schema::Value;
 use tantivy::schema::*;
-use tantivy::{doc, Index, IndexWriter, ReloadPolicy};
-use tokio::sync::RwLock;
-
-use crate::schemas::CloudEvent;
+use tantivy::snippet::SnippetGenerator;
+use tantivy::tokenizer::{
+    Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, TextAnalyzer,
+};
+use tantivy::{doc, DocId, Index, IndexWriter, ReloadPolicy, Score, SegmentReader};
+use tokio::sync::{mpsc, oneshot, watch, RwLock};
+
+use crate::error::error_field;
+pub use crate::error::StorageError;
+use crate::projection::{self, ResourceChange};
+use crate::schemas::{CloudEvent, JSONCommit};
+use crate::types::{PushDeliveryStats, PushSubscription};
+use crate::validation;
 
 // Define redb tables
 // EVENTS_BY_SEQ maps zero-padded sequence keys to serialized event records so iteration is lexicographic by sequence
@@ -26,6 +42,59 @@ const EVENTS_BY_SEQ_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("
 const RESOURCES_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("resources");
 /// Meta table for storing counters and small metadata (e.g. last assigned sequence)
 const META_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("meta");
+/// Maps (source, id) of every stored CloudEvent to its sequence key, used to reject duplicates.
+/// The CloudEvents spec requires `source` + `id` to be unique per distinct event.
+const EVENTS_BY_SOURCE_ID_TABLE: TableDefinition<(&str, &str), &str> =
+    TableDefinition::new("events_by_source_id");
+/// Maps event id to its sequence key for direct lookups. If different sources reuse an id,
+/// the most recently stored event wins.
+const EVENTS_BY_ID_TABLE: TableDefinition<&str, &str> = TableDefinition::new("events_by_id");
+/// Maps resource id to the sequence keys of every event that changed it. Values of a redb
+/// multimap are kept sorted, so a resource's events come back in log order.
+const EVENTS_BY_RESOURCE_TABLE: MultimapTableDefinition<&str, &str> =
+    MultimapTableDefinition::new("events_by_resource");
+/// Web Push subscriptions keyed by their endpoint URL, stored as JSON. Subscriptions are not
+/// derived from the event log, so rebuilds and resets leave them alone.
+const PUSH_SUBSCRIPTIONS_TABLE: TableDefinition<&str, &str> =
+    TableDefinition::new("push_subscriptions");
+/// Delivery statistics per push subscription endpoint, stored as JSON. Removed together with
+/// the subscription.
+const PUSH_DELIVERY_STATS_TABLE: TableDefinition<&str, &str> =
+    TableDefinition::new("push_delivery_stats");
+
+/// Meta key set when an index change could not be applied after its database commit; the
+/// search index is rebuilt on the next start (or by [`Storage::reindex`]).
+const INDEX_STALE_KEY: &str = "search_index_stale";
+
+/// Version of the Tantivy schema built in [`Storage::new`]. Bump this when changing the
+/// schema; an index written with another version is dropped and rebuilt on startup.
+const SEARCH_SCHEMA_VERSION: u32 = 6;
+
+/// Name of the analyzer used for free-text fields: lowercased and stemmed as Dutch.
+const DUTCH_TOKENIZER: &str = "nl_stem";
+
+/// Edit distance used for fuzzy term matching unless a query asks for another one.
+pub const DEFAULT_FUZZINESS: u8 = 1;
+/// Largest edit distance Tantivy's fuzzy queries support.
+pub const MAX_FUZZINESS: u8 = 2;
+
+/// Version of the secondary indexes derived from `events_by_seq`.
+/// Bump this when adding an index so existing databases get backfilled on startup.
+const INDEX_VERSION: u32 = 3;
+
+/// `source` of the events that `DELETE /resources/{id}` stores.
+pub const DELETE_SOURCE: &str = "urn:sse-delta-snapshot:resources";
+
+/// Format a sequence number as the zero-padded key used in `events_by_seq`.
+pub fn sequence_key(seq: u128) -> String {
+    format!("{:020}", seq)
+}
+
+/// Normalize a client-supplied sequence (e.g. `42` or `00000000000000000042`) to a sequence key.
+/// Returns `None` when the value is not a valid sequence number.
+pub fn normalize_sequence(seq: &str) -> Option<String> {
+    seq.trim().parse::<u128>().ok().map(sequence_key)
+}
 
 /// Record for storing events
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +108,135 @@ pub struct EventRecord {
     pub data: String, // JSON serialized
 }
 
+impl EventRecord {
+    /// Convert a stored record back into a CloudEvent.
+    /// `seq_key` is the `events_by_seq` key the record was read from.
+    fn into_cloud_event(self, seq_key: &str) -> Result<CloudEvent, StorageError> {
+        let data: Option<JsonValue> = decode_json(seq_key, &self.data)?;
+        Ok(CloudEvent {
+            specversion: "1.0".to_string(),
+            id: self.id,
+            source: self.source,
+            subject: self.subject,
+            event_type: self.event_type,
+            time: self.time,
+            datacontenttype: Some("application/json".to_string()),
+            dataschema: None,
+            dataref: None,
+            sequence: Some(seq_key.to_string()),
+            sequencetype: None,
+            data,
+        })
+    }
+}
+
+/// Outcome of [`Storage::store_event`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreOutcome {
+    /// The event was appended to the log under a newly assigned sequence key.
+    Stored(String),
+    /// An event with the same `source` and `id` was already stored under this sequence key.
+    Duplicate(String),
+}
+
+impl StoreOutcome {
+    /// The sequence key of the stored event (newly assigned or original).
+    pub fn sequence(&self) -> &str {
+        match self {
+            StoreOutcome::Stored(seq) | StoreOutcome::Duplicate(seq) => seq,
+        }
+    }
+}
+
+/// Resources as an event left them, read in the transaction that stored it; see
+/// [`Storage::store_event_committed`]. Consumers that run later (push notifications) describe
+/// the event with these rather than with whatever the resources look like by then.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommittedState {
+    /// The resource the event changed (`None` when it was deleted or nothing changed)
+    pub resource: Option<JsonValue>,
+    /// The resource named by the event's `subject`, usually the issue it is about
+    pub subject: Option<JsonValue>,
+}
+
+/// Point in the event log to reconstruct a resource at, see [`Storage::resource_at`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsOf {
+    /// Include events up to and including this sequence key.
+    Sequence(String),
+    /// Include events whose `time` is at or before this instant.
+    Time(chrono::DateTime<chrono::Utc>),
+}
+
+/// Summary of [`Storage::rebuild_projection`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebuildReport {
+    /// Number of events read from the log
+    pub events_replayed: usize,
+    /// Number of resources in the projection after the rebuild
+    pub resources_written: usize,
+    /// Events that could not be applied (they are kept in the log, but change nothing)
+    pub failures: Vec<ReplayFailure>,
+}
+
+/// An event that failed to apply during a projection rebuild.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayFailure {
+    pub sequence: String,
+    pub event_id: String,
+    pub error: String,
+}
+
+/// Summary of [`Storage::reindex`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReindexReport {
+    pub resources_indexed: usize,
+    pub events_indexed: usize,
+}
+
+/// Result of [`Storage::check_index`]: documents that exist on one side only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexCheckReport {
+    /// Number of resources and events in redb
+    pub db_ids: usize,
+    /// Number of distinct resource and event documents in the search index
+    pub index_ids: usize,
+    pub missing_from_index: Vec<IndexedDoc>,
+    pub missing_from_db: Vec<IndexedDoc>,
+}
+
+/// A resource or an event, as compared by [`Storage::check_index`]. Resources and events
+/// are told apart because their ids can coincide.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct IndexedDoc {
+    /// `resource` or `event`
+    pub kind: String,
+    pub id: String,
+}
+
+impl IndexedDoc {
+    fn resource(id: &str) -> Self {
+        Self {
+            kind: "resource".to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    fn event(id: &str) -> Self {
+        Self {
+            kind: "event".to_string(),
+            id: id.to_string(),
+        }
+    }
+}
+
+impl IndexCheckReport {
+    /// Whether redb and the search index contain the same ids.
+    pub fn is_consistent(&self) -> bool {
+        self.missing_from_index.is_empty() && self.missing_from_db.is_empty()
+    }
+}
+
 /// Record for storing resources
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceRecord {
@@ -48,20 +246,48 @@ pub struct ResourceRecord {
     pub updated_at: String,
 }
 
+impl ResourceRecord {
+    /// The resource's JSON data.
+    fn json_data(&self) -> Result<JsonValue, StorageError> {
+        decode_json(&self.id, &self.data)
+    }
+}
+
 /// Storage layer combining redb K/V store and Tantivy search
 pub struct Storage {
     db: Arc<Database>,
     search_index: Arc<Index>,
     search_writer: Arc<RwLock<IndexWriter>>,
+    /// Queue of index changes, applied in order by the background indexer
+    index_tx: mpsc::Sender<IndexOp>,
+    indexer: tokio::task::JoinHandle<()>,
+    /// Number of index commits made by the background indexer
+    index_commits: watch::Receiver<u64>,
     id_field: Field,
+    /// Only set on resource documents, so replacing or removing one leaves event documents
+    /// that happen to share its id alone
+    resource_id_field: Field,
     type_field: Field,
     content_field: Field,
     timestamp_field: Field,
+    title_field: Field,
+    description_field: Field,
+    status_field: Field,
+    assignee_field: Field,
+    deadline_field: Field,
+    actor_field: Field,
+    issue_id_field: Field,
+    facet_field: Field,
 }
 
 impl Storage {
-    /// Create a new storage instance
-    pub async fn new(data_dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Create a new storage instance with the default index commit policy
+    pub async fn new(data_dir: &Path) -> Result<Self, StorageError> {
+        Self::with_config(data_dir, IndexConfig::default()).await
+    }
+
+    /// Create a new storage instance that commits the search index according to `config`
+    pub async fn with_config(data_dir: &Path, config: IndexConfig) -> Result<Self, StorageError> {
         // Create directories
         let db_path = data_dir.join("data.redb");
         let index_path = data_dir.join("search_index");
@@ -78,265 +304,585 @@ impl Storage {
             let _ = write_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
             let _ = write_txn.open_table(RESOURCES_TABLE)?;
             let _ = write_txn.open_table(META_TABLE)?;
+            let _ = write_txn.open_table(EVENTS_BY_SOURCE_ID_TABLE)?;
+            let _ = write_txn.open_table(EVENTS_BY_ID_TABLE)?;
+            let _ = write_txn.open_multimap_table(EVENTS_BY_RESOURCE_TABLE)?;
+            let _ = write_txn.open_table(PUSH_SUBSCRIPTIONS_TABLE)?;
+            let _ = write_txn.open_table(PUSH_DELIVERY_STATS_TABLE)?;
         }
         write_txn.commit()?;
 
+        Self::backfill_indexes(&db)?;
+
         // Initialize Tantivy search index
+        let dutch_text = TextOptions::default()
+            .set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer(DUTCH_TOKENIZER)
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            )
+            .set_stored();
         let mut schema_builder = Schema::builder();
         let id_field = schema_builder.add_text_field("id", STRING | STORED);
+        let resource_id_field = schema_builder.add_text_field("resource_id", STRING | STORED);
         let type_field = schema_builder.add_text_field("type", STRING | STORED);
-        let content_field = schema_builder.add_text_field("content", TEXT | STORED);
-        let timestamp_field = schema_builder.add_date_field("timestamp", INDEXED | STORED);
+        let content_field = schema_builder.add_text_field("content", dutch_text.clone());
+        let timestamp_field = schema_builder.add_date_field("timestamp", INDEXED | STORED | FAST);
+        // Structured fields, taken from the resource types in `schemas.rs`
+        let title_field = schema_builder.add_text_field("title", dutch_text.clone());
+        let description_field = schema_builder.add_text_field("description", dutch_text);
+        let status_field = schema_builder.add_text_field("status", STRING | STORED);
+        let assignee_field = schema_builder.add_text_field("assignee", STRING | STORED);
+        let deadline_field = schema_builder.add_text_field("deadline", STRING | STORED);
+        let actor_field = schema_builder.add_text_field("actor", STRING | STORED);
+        let issue_id_field = schema_builder.add_text_field("issue_id", STRING | STORED);
+        let facet_field = schema_builder.add_facet_field("facet", FacetOptions::default());
         let schema = schema_builder.build();
 
-        let index = if index_path.exists() && std::fs::read_dir(&index_path)?.next().is_some() {
-            Index::open_in_dir(&index_path)?
-        } else {
+        let schema_version = {
+            let read_txn = db.begin_read()?;
+            let meta = read_txn.open_table(META_TABLE)?;
+            let version = meta
+                .get("search_schema_version")?
+                .and_then(|g| std::str::from_utf8(g.value()).ok()?.parse::<u32>().ok());
+            version
+        };
+        let index_stale = db
+            .begin_read()?
+            .open_table(META_TABLE)?
+            .get(INDEX_STALE_KEY)?
+            .is_some();
+        let index_exists = std::fs::read_dir(&index_path)?.next().is_some();
+        let recreate_index = !index_exists || schema_version != Some(SEARCH_SCHEMA_VERSION);
+        let rebuild_index = recreate_index || index_stale;
+
+        let index = if recreate_index {
+            if index_exists {
+                println!(
+                    "[storage] search schema changed ({:?} -> {}), recreating index",
+                    schema_version, SEARCH_SCHEMA_VERSION
+                );
+                std::fs::remove_dir_all(&index_path)?;
+                std::fs::create_dir_all(&index_path)?;
+            }
             Index::create_in_dir(&index_path, schema.clone())?
+        } else {
+            if index_stale {
+                println!("[storage] search index missed changes, rebuilding it");
+            }
+            Index::open_in_dir(&index_path)?
         };
 
-        let search_writer_inner = index.writer(50_000_000)?; // 50MB heap
-                                                             // Wrap the writer in an Arc<RwLock<_>> so we can share it with background commit task.
-        let search_writer = Arc::new(RwLock::new(search_writer_inner));
-
-        // Spawn a background periodic committer that flushes the writer every 10 seconds.
-        // This allows many add_document calls to be batched into fewer commits,
-        // reducing indexing latency during high-throughput operations (like startup seeding).
-        {
-            let writer_for_committer = search_writer.clone();
-            tokio::spawn(async move {
-                loop {
-                    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-                    // Acquire write lock and commit; log errors but keep looping.
-                    let mut w = writer_for_committer.write().await;
-                    if let Err(err) = w.commit() {
-                        eprintln!("[storage][bg] periodic commit failed: {}", err);
-                    } else {
-                        println!("[storage][bg] periodic commit completed");
-                    }
-                }
-            });
-        }
-
-        Ok(Self {
+        index
+            .tokenizers()
+            .register(DUTCH_TOKENIZER, dutch_analyzer());
+
+        let search_writer = Arc::new(RwLock::new(index.writer(50_000_000)?)); // 50MB heap
+
+        // Index changes are queued and applied by one background task, which batches them
+        // into commits according to the commit policy. Writers wait while the queue is full.
+        let (index_tx, index_rx) = mpsc::channel(config.queue_capacity.max(1));
+        let (commits_tx, index_commits) = watch::channel(0);
+        let indexer = tokio::spawn(run_indexer(
+            index_rx,
+            search_writer.clone(),
+            resource_id_field,
+            config,
+            commits_tx,
+        ));
+
+        let storage = Self {
             db: Arc::new(db),
             search_index: Arc::new(index),
             search_writer,
+            index_tx,
+            indexer,
+            index_commits,
             id_field,
+            resource_id_field,
             type_field,
             content_field,
             timestamp_field,
-        })
+            title_field,
+            description_field,
+            status_field,
+            assignee_field,
+            deadline_field,
+            actor_field,
+            issue_id_field,
+            facet_field,
+        };
+
+        if rebuild_index {
+            storage.reindex().await?;
+            let write_txn = storage.db.begin_write()?;
+            {
+                let mut meta = write_txn.open_table(META_TABLE)?;
+                let version = SEARCH_SCHEMA_VERSION.to_string();
+                meta.insert("search_schema_version", version.as_bytes())?;
+            }
+            write_txn.commit()?;
+        }
+
+        Ok(storage)
+    }
+
+    /// Populate secondary indexes for events stored before those indexes existed.
+    /// Runs once per `INDEX_VERSION` bump; the version is recorded in the meta table.
+    fn backfill_indexes(db: &Database) -> Result<(), StorageError> {
+        let write_txn = db.begin_write()?;
+        {
+            let mut meta = write_txn.open_table(META_TABLE)?;
+            let current = meta
+                .get("index_version")?
+                .and_then(|g| std::str::from_utf8(g.value()).ok()?.parse::<u32>().ok())
+                .unwrap_or(0);
+            if current >= INDEX_VERSION {
+                return Ok(());
+            }
+
+            println!(
+                "[storage] backfilling event indexes from version {} to {}",
+                current, INDEX_VERSION
+            );
+
+            let events = write_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
+            let mut by_source_id = write_txn.open_table(EVENTS_BY_SOURCE_ID_TABLE)?;
+            let mut by_id = write_txn.open_table(EVENTS_BY_ID_TABLE)?;
+            let mut by_resource = write_txn.open_multimap_table(EVENTS_BY_RESOURCE_TABLE)?;
+            for item in events.iter()? {
+                let (key, value) = item?;
+                let rec: EventRecord = decode(key.value(), value.value())?;
+                by_id.insert(rec.id.as_str(), key.value())?;
+
+                // Events with a malformed commit never changed a resource; leave them out.
+                let event = rec.clone().into_cloud_event(key.value())?;
+                if let Ok(Some(resource_id)) = projection::resource_id(&event) {
+                    by_resource.insert(resource_id.as_str(), key.value())?;
+                }
+                // Keep the first occurrence when the log already contains duplicates.
+                if by_source_id
+                    .get((rec.source.as_str(), rec.id.as_str()))?
+                    .is_none()
+                {
+                    by_source_id.insert((rec.source.as_str(), rec.id.as_str()), key.value())?;
+                }
+            }
+
+            meta.insert("index_version", INDEX_VERSION.to_string().as_bytes())?;
+        }
+        write_txn.commit()?;
+        Ok(())
     }
 
     /// Store an event in the K/V store (with diagnostic logging) and assign a monotonically increasing sequence.
-    /// Returns the assigned sequence string (zero-padded) on success.
-    pub async fn store_event(
+    ///
+    /// Sequence allocation, the event insert and the resulting resource change (see
+    /// [`crate::projection`]) are committed in a single write transaction, so the event log and
+    /// the resources projection can never diverge and no sequence numbers are burned. If the
+    /// event cannot be applied (e.g. a malformed JSONCommit) nothing is written.
+    ///
+    /// Events are idempotent on (`source`, `id`): storing an event that was already accepted
+    /// returns [`StoreOutcome::Duplicate`] with the originally assigned sequence and writes nothing.
+    pub async fn store_event(&self, event: &CloudEvent) -> Result<StoreOutcome, StorageError> {
+        Ok(self.store_event_committed(event).await?.0)
+    }
+
+    /// Like [`Storage::store_event`], also returning the resources as the event left them
+    /// (empty for duplicates).
+    pub async fn store_event_committed(
         &self,
         event: &CloudEvent,
-    ) -> Result<String, Box<dyn std::error::Error>> {
+    ) -> Result<(StoreOutcome, CommittedState), StorageError> {
         // Diagnostic: log attempt to store event
         println!(
             "[storage] attempt store_event: id={} type={} source={}",
             event.id, event.event_type, event.source
         );
 
-        // Atomically get the next sequence number using the META_TABLE.
-        // We perform this with a small write transaction that reads the last_seq value,
-        // increments it, writes it back and commits. The new sequence is then returned.
-        let seq = {
-            // start a write txn to update the counter atomically
-            let mut wtx = self.db.begin_write()?;
-            // Read, compute and write within an inner scope so the table guard is dropped
-            // before committing the transaction (avoids borrow conflicts).
-            let next = {
-                let mut meta = wtx.open_table(META_TABLE)?;
-
-                // Try to read the last sequence value and convert to owned bytes immediately.
-                let last_seq_bytes = match meta.get("last_seq")? {
-                    Some(g) => Some(g.value().to_vec()),
-                    None => None,
-                };
+        // redb serializes write transactions, so the duplicate check, the sequence counter and
+        // the projection all observe a consistent state.
+        let write_txn = self.db.begin_write()?;
+        let (seq_key, change, committed) = {
+            let mut by_source_id = write_txn.open_table(EVENTS_BY_SOURCE_ID_TABLE)?;
+            let existing = by_source_id
+                .get((event.source.as_str(), event.id.as_str()))?
+                .map(|g| g.value().to_string());
+            if let Some(original_seq) = existing {
+                println!(
+                    "[storage] duplicate event ignored: id={} source={} seq={}",
+                    event.id, event.source, original_seq
+                );
+                return Ok((
+                    StoreOutcome::Duplicate(original_seq),
+                    CommittedState::default(),
+                ));
+            }
 
-                // Compute next sequence (u128) robustly
-                let next_seq: u128 = if let Some(bytes) = last_seq_bytes {
-                    match std::str::from_utf8(&bytes)
-                        .ok()
-                        .and_then(|s| s.parse::<u128>().ok())
-                    {
-                        Some(val) => val + 1,
-                        None => 1u128,
-                    }
-                } else {
-                    1u128
-                };
+            let mut meta = write_txn.open_table(META_TABLE)?;
 
-                // store back the new last_seq as bytes
-                meta.insert("last_seq", next_seq.to_string().as_bytes())?;
+            // Try to read the last sequence value and convert to owned bytes immediately.
+            let last_seq_bytes = meta.get("last_seq")?.map(|g| g.value().to_vec());
 
-                // drop meta (end of inner scope) so we can commit safely
-                next_seq
+            // Compute next sequence (u128) robustly
+            let next_seq: u128 = if let Some(bytes) = last_seq_bytes {
+                match std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| s.parse::<u128>().ok())
+                {
+                    Some(val) => val + 1,
+                    None => 1u128,
+                }
+            } else {
+                1u128
             };
+            // sequence keys have a fixed width (20 digits) to ensure lexicographic ordering
+            let seq_key = sequence_key(next_seq);
+
+            meta.insert("last_seq", next_seq.to_string().as_bytes())?;
+            by_source_id.insert((event.source.as_str(), event.id.as_str()), seq_key.as_str())?;
+
+            // Create record and include sequence as string
+            let record = EventRecord {
+                id: event.id.clone(),
+                event_type: event.event_type.clone(),
+                source: event.source.clone(),
+                subject: event.subject.clone(),
+                time: event.time.clone(),
+                sequence: Some(seq_key.clone()),
+                data: serde_json::to_string(&event.data)?,
+            };
+            let serialized = bincode::serialize(&record)?;
 
-            // commit the write transaction after the table guard has been dropped
-            wtx.commit()?;
-
-            next
-        };
-
-        // Create record and include sequence as string
-        let record = EventRecord {
-            id: event.id.clone(),
-            event_type: event.event_type.clone(),
-            source: event.source.clone(),
-            subject: event.subject.clone(),
-            time: event.time.clone(),
-            sequence: Some(seq.to_string()),
-            data: serde_json::to_string(&event.data)?,
-        };
-
-        let serialized = bincode::serialize(&record)?;
-
-        // Write seq->record mapping (we store only sequence keyed records for ordered iteration)
-        let write_txn = self.db.begin_write()?;
-        {
             let mut seq_table = write_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
-            // create sequence key with fixed width (e.g. 020 digits) to ensure lexicographic ordering
-            let seq_key = format!("{:020}", seq);
             seq_table.insert(seq_key.as_str(), serialized.as_slice())?;
-        }
+            let mut by_id = write_txn.open_table(EVENTS_BY_ID_TABLE)?;
+            by_id.insert(event.id.as_str(), seq_key.as_str())?;
+
+            // Apply the event to the resources projection within the same transaction
+            let updated_at = chrono::Utc::now().to_rfc3339();
+            let change = project_event(&write_txn, event, &seq_key, &updated_at)?;
+            // Live writes must match the resource schema the commit names; returning here
+            // aborts the transaction, so nothing is stored
+            if let Some(ResourceChange::Upsert { data, .. }) = &change {
+                check_resource_schema(event, data)?;
+            }
+            let committed = committed_state(&write_txn, event, change.as_ref())?;
+
+            (seq_key, change.map(|c| (c, updated_at)), committed)
+        };
         write_txn.commit()?;
 
         // Diagnostic: confirm persisted to DB
-        let seq_key = format!("{:020}", seq);
         println!(
             "[storage] persisted event to DB: id={} seq={}",
             event.id, seq_key
         );
 
-        // Schedule background indexing for the event (do not block the store operation)
+        // Update the search index after the commit (do not block the store operation)
+        self.schedule_event_index(event, &seq_key).await;
+        match change {
+            Some((
+                ResourceChange::Upsert {
+                    id,
+                    resource_type,
+                    data,
+                },
+                updated_at,
+            )) => {
+                println!(
+                    "[storage] applied event id={} to resource id={} type={}",
+                    event.id, id, resource_type
+                );
+                let issue_id = projection::parent_issue_id(event, &id);
+                self.schedule_resource_index(
+                    &id,
+                    &resource_type,
+                    &data,
+                    &resource_time(event, &updated_at),
+                    issue_id.as_deref(),
+                )
+                .await;
+            }
+            Some((ResourceChange::Delete { id }, _)) => {
+                println!(
+                    "[storage] applied event id={} deleting resource id={}",
+                    event.id, id
+                );
+                // The event is committed; a failing index must not turn it into an error
+                if let Err(e) = self.remove_from_index(&id).await {
+                    eprintln!(
+                        "[storage] failed to remove resource id={} from search index: {}",
+                        id, e
+                    );
+                    self.mark_index_stale();
+                }
+            }
+            None => {}
+        }
+
+        // Return the assigned sequence key to the caller
+        Ok((StoreOutcome::Stored(seq_key), committed))
+    }
+
+    /// Queue an event for indexing (committed according to the commit policy).
+    async fn schedule_event_index(&self, event: &CloudEvent, seq_key: &str) {
         println!(
-            "[storage] scheduling background index for event: id={}",
-            event.id
+            "[storage] scheduling background index for event: id={} seq={}",
+            event.id, seq_key
         );
 
-        // Clone the pieces we need to move into the background task
-        let event_for_index = event.clone();
-        let search_writer = self.search_writer.clone();
-        let id_field = self.id_field;
-        let type_field = self.type_field;
-        let content_field = self.content_field;
-        let timestamp_field = self.timestamp_field;
-        let seq_for_log = seq_key.clone();
+        self.queue_index_op(IndexOp::Add {
+            doc: self.event_document(event),
+            replace: None,
+        })
+        .await;
+    }
 
-        // Spawn a background task to perform indexing asynchronously.
-        tokio::spawn(async move {
-            println!(
-                "[storage][bg] start indexing event: id={} seq={}",
-                event_for_index.id, seq_for_log
-            );
+    /// Remember that the search index missed a change, so it is rebuilt on the next start.
+    fn mark_index_stale(&self) {
+        let marked = (|| -> Result<(), StorageError> {
+            let write_txn = self.db.begin_write()?;
+            write_txn
+                .open_table(META_TABLE)?
+                .insert(INDEX_STALE_KEY, b"1".as_slice())?;
+            write_txn.commit()?;
+            Ok(())
+        })();
+        match marked {
+            Ok(()) => eprintln!("[storage] search index marked stale; it is rebuilt on restart"),
+            Err(e) => eprintln!("[storage] failed to mark search index stale: {}", e),
+        }
+    }
 
-            // Build timestamp
-            let timestamp = if let Some(time_str) = &event_for_index.time {
-                chrono::DateTime::parse_from_rfc3339(time_str)
-                    .ok()
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-            } else {
-                Some(chrono::Utc::now())
-            };
+    /// Hand an operation to the background indexer, waiting while its queue is full. When
+    /// the indexer has stopped, the index is marked stale instead.
+    async fn queue_index_op(&self, op: IndexOp) {
+        if self.index_tx.send(op).await.is_err() {
+            eprintln!("[storage] search indexer stopped; index change dropped");
+            self.mark_index_stale();
+        }
+    }
 
-            // Create searchable content from event data (same logic as index_event)
-            let content = format!(
-                "{} {} {} {}",
-                event_for_index.event_type,
-                event_for_index.source,
-                event_for_index.subject.as_deref().unwrap_or(""),
-                event_for_index
-                    .data
-                    .as_ref()
-                    .map(|v| v.to_string())
-                    .unwrap_or_default()
-            );
+    /// Commit every index change queued so far and wait until it is searchable.
+    pub async fn flush_index(&self) -> Result<(), StorageError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let stopped = || StorageError::Index("search indexer stopped".to_string());
+        self.index_tx
+            .send(IndexOp::Flush(reply_tx))
+            .await
+            .map_err(|_| stopped())?;
+        reply_rx
+            .await
+            .map_err(|_| stopped())?
+            .map_err(StorageError::Index)
+    }
 
-            // Acquire writer and add document
-            let writer = search_writer.write().await;
-            let mut doc = doc!(
-                id_field => event_for_index.id.as_str(),
-                type_field => event_for_index.event_type.as_str(),
-                content_field => content.as_str(),
-            );
+    /// Watch the number of search index commits; it changes whenever queued changes become
+    /// searchable.
+    pub fn index_commits(&self) -> watch::Receiver<u64> {
+        self.index_commits.clone()
+    }
 
-            if let Some(ts) = timestamp {
-                doc.add_date(
-                    timestamp_field,
-                    tantivy::DateTime::from_timestamp_secs(ts.timestamp()),
-                );
-            }
+    /// Shut the storage down cleanly: commit all queued index changes, stop the background
+    /// indexer, wait for Tantivy's merge threads and close the database.
+    pub async fn close(self) -> Result<(), StorageError> {
+        self.flush_index().await?;
 
-            // Perform the add_document (commit deferred to periodic committer)
-            if let Err(e) = (|| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-                writer.add_document(doc)?;
-                // Commit deferred to periodic committer to reduce per-document latency
-                Ok(())
-            })() {
-                eprintln!(
-                    "[storage][bg] failed adding document for event id={} seq={} error={}",
-                    event_for_index.id, seq_for_log, e
-                );
-            } else {
-                println!(
-                    "[storage][bg] added doc for event id={} seq={} (commit deferred)",
-                    event_for_index.id, seq_for_log
-                );
-            }
-        });
+        let Self {
+            db,
+            search_writer,
+            index_tx,
+            indexer,
+            ..
+        } = self;
+        drop(index_tx);
+        indexer
+            .await
+            .map_err(|e| StorageError::Index(e.to_string()))?;
 
-        // Return the assigned sequence key to the caller
-        Ok(seq_key)
+        match Arc::try_unwrap(search_writer) {
+            Ok(writer) => writer.into_inner().wait_merging_threads()?,
+            Err(_) => eprintln!("[storage] index writer still in use; skipping merge wait"),
+        }
+        drop(db);
+        println!("[storage] closed");
+        Ok(())
+    }
+
+    /// Get an event by ID (looked up through the `events_by_id` index)
+    pub async fn get_event(&self, id: &str) -> Result<Option<CloudEvent>, StorageError> {
+        let read_txn = self.db.begin_read()?;
+        let by_id = read_txn.open_table(EVENTS_BY_ID_TABLE)?;
+
+        let seq_key = match by_id.get(id)? {
+            Some(seq) => seq.value().to_string(),
+            None => return Ok(None),
+        };
+
+        let table = read_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
+        match table.get(seq_key.as_str())? {
+            Some(value) => {
+                let rec: EventRecord = decode(&seq_key, value.value())?;
+                Ok(Some(rec.into_cloud_event(&seq_key)?))
+            }
+            None => Ok(None),
+        }
     }
 
-    /// Get an event by ID (scan events_by_seq and return the matching event)
-    #[allow(dead_code)]
-    pub async fn get_event(
+    /// Get an event by its sequence key (as returned by [`Storage::store_event`]).
+    pub async fn get_event_by_sequence(
         &self,
-        id: &str,
-    ) -> Result<Option<CloudEvent>, Box<dyn std::error::Error>> {
+        seq_key: &str,
+    ) -> Result<Option<CloudEvent>, StorageError> {
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
 
-        let iter = table.iter()?;
-        for item in iter {
-            let (_key, value) = item?;
-            let rec: EventRecord = bincode::deserialize(value.value())?;
-            if rec.id == id {
-                let data: Option<JsonValue> = serde_json::from_str(&rec.data)?;
-                return Ok(Some(CloudEvent {
-                    specversion: "1.0".to_string(),
-                    id: rec.id,
-                    source: rec.source,
-                    subject: rec.subject,
-                    event_type: rec.event_type,
-                    time: rec.time,
-                    datacontenttype: Some("application/json".to_string()),
-                    dataschema: None,
-                    dataref: None,
-                    sequence: rec.sequence,
-                    sequencetype: None,
-                    data,
-                }));
+        match table.get(seq_key)? {
+            Some(value) => {
+                let rec: EventRecord = decode(seq_key, value.value())?;
+                Ok(Some(rec.into_cloud_event(seq_key)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// List the events that changed a resource, in sequence order (oldest first).
+    ///
+    /// This includes the commit that deleted the resource, so the history of deleted
+    /// resources remains available for auditing.
+    pub async fn resource_history(
+        &self,
+        resource_id: &str,
+    ) -> Result<Vec<CloudEvent>, StorageError> {
+        let read_txn = self.db.begin_read()?;
+        let by_resource = read_txn.open_multimap_table(EVENTS_BY_RESOURCE_TABLE)?;
+        let events = read_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
+
+        let mut results = Vec::new();
+        for seq in by_resource.get(resource_id)? {
+            let seq = seq?;
+            if let Some(value) = events.get(seq.value())? {
+                let rec: EventRecord = decode(seq.value(), value.value())?;
+                results.push(rec.into_cloud_event(seq.value())?);
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Reconstruct a resource as it was at a point in the event log.
+    ///
+    /// Folds the resource's history (see [`Storage::resource_history`]) through the same
+    /// projection as live writes, leaving out the events after `as_of`. Returns `None` if the
+    /// resource did not exist (yet, or anymore) at that point. For [`AsOf::Time`], events are
+    /// still applied in log order, but each is kept or left out by its own `time`: producer
+    /// clocks are not monotonic, so a later event can carry an earlier time. Events without a
+    /// parsable `time` are always applied.
+    pub async fn resource_at(
+        &self,
+        resource_id: &str,
+        as_of: &AsOf,
+    ) -> Result<Option<JsonValue>, StorageError> {
+        let history = self.resource_history(resource_id).await?;
+
+        let mut state: Option<JsonValue> = None;
+        for event in history {
+            let after_point = match as_of {
+                AsOf::Sequence(seq) => event.sequence.as_deref().is_some_and(|s| s > seq.as_str()),
+                AsOf::Time(at) => event
+                    .time
+                    .as_deref()
+                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                    .is_some_and(|t| t > *at),
+            };
+            if after_point {
+                continue;
+            }
+
+            let change = projection::apply_event(&event, state.clone())
+                .map_err(|e| invalid_event(&event, e))?;
+            state = match change {
+                Some(ResourceChange::Upsert { data, .. }) => Some(data),
+                Some(ResourceChange::Delete { .. }) => None,
+                None => state,
+            };
+        }
+
+        Ok(state)
+    }
+
+    /// Rebuild the resources projection from the event log.
+    ///
+    /// Truncates the `resources` table and the per-resource history index, then replays every
+    /// event in sequence order through the same JSONCommit handling as live writes. Use this
+    /// after the projection got corrupted or when the projection logic changed. Everything
+    /// happens in one write transaction, so concurrent writers wait and readers see either the
+    /// old or the new projection. The search index is not touched.
+    pub async fn rebuild_projection(&self) -> Result<RebuildReport, StorageError> {
+        println!("[storage] rebuilding resources projection from event log");
+
+        let mut report = RebuildReport::default();
+        let write_txn = self.db.begin_write()?;
+        {
+            write_txn.delete_table(RESOURCES_TABLE)?;
+            write_txn.delete_multimap_table(EVENTS_BY_RESOURCE_TABLE)?;
+
+            let events = write_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
+            let updated_at = chrono::Utc::now().to_rfc3339();
+            for item in events.iter()? {
+                let (key, value) = item?;
+                let seq_key = key.value().to_string();
+                report.events_replayed += 1;
+
+                let rec: EventRecord = match bincode::deserialize(value.value()) {
+                    Ok(rec) => rec,
+                    Err(e) => {
+                        report.failures.push(ReplayFailure {
+                            sequence: seq_key,
+                            event_id: String::new(),
+                            error: format!("corrupt event record: {}", e),
+                        });
+                        continue;
+                    }
+                };
+                let event_id = rec.id.clone();
+
+                let applied = rec
+                    .into_cloud_event(&seq_key)
+                    .and_then(|event| project_event(&write_txn, &event, &seq_key, &updated_at));
+                if let Err(e) = applied {
+                    report.failures.push(ReplayFailure {
+                        sequence: seq_key,
+                        event_id,
+                        error: e.to_string(),
+                    });
+                }
             }
+
+            report.resources_written = write_txn.open_table(RESOURCES_TABLE)?.len()? as usize;
         }
+        write_txn.commit()?;
 
-        Ok(None)
+        println!(
+            "[storage] projection rebuilt: events={} resources={} failures={}",
+            report.events_replayed,
+            report.resources_written,
+            report.failures.len()
+        );
+
+        Ok(report)
     }
 
-    /// Store a resource in the K/V store (with diagnostic logging)
+    /// Store a resource in the K/V store (with diagnostic logging). Bypasses the event log,
+    /// so tests only.
+    #[cfg(test)]
     pub async fn store_resource(
         &self,
         id: &str,
         resource_type: &str,
         data: &JsonValue,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), StorageError> {
         // Diagnostic: log attempt to store resource
         println!(
             "[storage] attempt store_resource: id={} type={}",
@@ -365,111 +911,485 @@ impl Storage {
         println!("[storage] persisted resource to DB: id={}", id);
 
         // Schedule background indexing for the resource (do not block the store operation)
+        self.schedule_resource_index(id, resource_type, data, &timestamp, None)
+            .await;
+
+        Ok(())
+    }
+
+    /// Queue a resource for indexing, replacing its previous document (committed according
+    /// to the commit policy).
+    async fn schedule_resource_index(
+        &self,
+        id: &str,
+        resource_type: &str,
+        data: &JsonValue,
+        timestamp: &str,
+        issue_id: Option<&str>,
+    ) {
         println!(
             "[storage] scheduling background index for resource: id={} type={}",
             id, resource_type
         );
 
-        // Clone the pieces we need to move into the background task
-        let resource_id = id.to_string();
-        let resource_type_cloned = resource_type.to_string();
-        let data_for_index = data.clone();
-        let timestamp_for_index = timestamp.clone();
-        let search_writer = self.search_writer.clone();
-        let id_field = self.id_field;
-        let type_field = self.type_field;
-        let content_field = self.content_field;
-        let timestamp_field = self.timestamp_field;
-
-        tokio::spawn(async move {
-            println!(
-                "[storage][bg] start indexing resource: id={} type={}",
-                resource_id, resource_type_cloned
-            );
+        self.queue_index_op(IndexOp::Add {
+            doc: self.resource_document(id, resource_type, data, timestamp, issue_id),
+            replace: Some(id.to_string()),
+        })
+        .await;
+    }
 
-            // parse timestamp, fallback to now
-            let ts = chrono::DateTime::parse_from_rfc3339(&timestamp_for_index)
+    /// Build the search document for an event.
+    fn event_document(&self, event: &CloudEvent) -> TantivyDocument {
+        // Build timestamp
+        let timestamp = if let Some(time_str) = &event.time {
+            chrono::DateTime::parse_from_rfc3339(time_str)
                 .ok()
                 .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(chrono::Utc::now);
-
-            // Create searchable content from resource data
-            let content = data_for_index.to_string();
-
-            // Acquire the writer and index document
-            let writer = search_writer.write().await;
-            let doc = doc!(
-                id_field => resource_id.as_str(),
-                type_field => resource_type_cloned.as_str(),
-                content_field => content.as_str(),
-                timestamp_field => tantivy::DateTime::from_timestamp_secs(ts.timestamp()),
-            );
+        } else {
+            Some(chrono::Utc::now())
+        };
 
-            if let Err(e) = (|| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-                writer.add_document(doc)?;
-                // Commit deferred to periodic committer to reduce per-document latency
-                Ok(())
-            })() {
-                eprintln!(
-                    "[storage][bg] failed adding document for resource id={} error={}",
-                    resource_id, e
-                );
-            } else {
-                println!(
-                    "[storage][bg] added doc for resource id={} (commit deferred)",
-                    resource_id
-                );
+        // Create searchable content from event data
+        let content = format!(
+            "{} {} {} {}",
+            event.event_type,
+            event.source,
+            event.subject.as_deref().unwrap_or(""),
+            event
+                .data
+                .as_ref()
+                .map(|v| v.to_string())
+                .unwrap_or_default()
+        );
+
+        let mut doc = doc!(
+            self.id_field => event.id.as_str(),
+            self.type_field => event.event_type.as_str(),
+            self.content_field => content.as_str(),
+            self.facet_field => Facet::from_path(["type", event.event_type.as_str()]),
+        );
+
+        if projection::is_json_commit(event) {
+            let actor = event.data.as_ref().and_then(|d| d.get("actor"));
+            if let Some(actor) = actor.and_then(|a| a.as_str()) {
+                doc.add_text(self.actor_field, actor);
             }
-        });
+        }
 
-        Ok(())
+        if let Some(ts) = timestamp {
+            doc.add_date(
+                self.timestamp_field,
+                tantivy::DateTime::from_timestamp_secs(ts.timestamp()),
+            );
+        }
+
+        doc
     }
 
-    /// Get a resource by ID
-    pub async fn get_resource(
+    /// Build the search document for a resource. `issue_id` links comments, tasks and other
+    /// timeline items to the issue they belong to.
+    fn resource_document(
         &self,
         id: &str,
-    ) -> Result<Option<JsonValue>, Box<dyn std::error::Error>> {
-        let read_txn = self.db.begin_read()?;
-        let table = read_txn.open_table(RESOURCES_TABLE)?;
-
-        let result = table.get(id)?;
+        resource_type: &str,
+        data: &JsonValue,
+        timestamp: &str,
+        issue_id: Option<&str>,
+    ) -> TantivyDocument {
+        // parse timestamp, fallback to now
+        let ts = chrono::DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .unwrap_or_else(chrono::Utc::now);
+
+        // Create searchable content from resource data
+        let content = data.to_string();
+
+        let mut doc = doc!(
+            self.id_field => id,
+            self.resource_id_field => id,
+            self.type_field => resource_type,
+            self.content_field => content.as_str(),
+            self.timestamp_field => tantivy::DateTime::from_timestamp_secs(ts.timestamp()),
+            self.facet_field => Facet::from_path(["type", resource_type]),
+        );
 
-        match result {
-            Some(bytes) => {
-                let rec: ResourceRecord = bincode::deserialize(bytes.value())?;
-                let data: JsonValue = serde_json::from_str(&rec.data)?;
-                Ok(Some(data))
-            }
-            None => Ok(None),
+        let text = |key: &str| data.get(key).and_then(|v| v.as_str());
+        // Tasks have a call to action instead of a title
+        if let Some(title) = text("title").or_else(|| text("cta")) {
+            doc.add_text(self.title_field, title);
+        }
+        // Comments have no description; their text is the closest equivalent
+        if let Some(description) = text("description").or_else(|| text("content")) {
+            doc.add_text(self.description_field, description);
+        }
+        if let Some(assignee) = text("assignee") {
+            doc.add_text(self.assignee_field, assignee);
+        }
+        if let Some(deadline) = text("deadline") {
+            doc.add_text(self.deadline_field, deadline);
+        }
+        if let Some(issue_id) = issue_id {
+            doc.add_text(self.issue_id_field, issue_id);
+        }
+        // Tasks only track completion; expose it as a status so they can be filtered alike
+        let status = match data.get("completed").and_then(|v| v.as_bool()) {
+            Some(true) => Some("completed"),
+            Some(false) => Some("open"),
+            None => text("status"),
+        };
+        if let Some(status) = status {
+            doc.add_text(self.status_field, status);
+            doc.add_facet(self.facet_field, Facet::from_path(["status", status]));
         }
+
+        doc
     }
 
-    /// Delete a resource
-    pub async fn delete_resource(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// Rebuild the search index from the `resources` and `events_by_seq` tables.
+    ///
+    /// All existing documents are dropped and every resource and event is indexed again in a
+    /// single commit, so searches keep seeing the old index until the new one is complete.
+    /// If the rebuild fails, nothing changes.
+    pub async fn reindex(&self) -> Result<ReindexReport, StorageError> {
+        println!("[storage] rebuilding search index from database");
+
+        // Commit what is queued first: those changes are already in the database, and a
+        // failed rebuild rolls back everything uncommitted in the writer.
+        self.flush_index().await?;
+        // Hold the writer for the whole rebuild so background indexing waits for it.
+        let mut writer = self.search_writer.write().await;
+        let report = match self.rebuild_search_documents(&mut writer) {
+            Ok(report) => report,
+            Err(e) => {
+                // Discard the staged delete-all, or the indexer's next commit would empty
+                // the index
+                if let Err(rollback) = writer.rollback() {
+                    eprintln!("[storage] failed to roll back search index: {}", rollback);
+                }
+                return Err(e);
+            }
+        };
+        drop(writer);
+
         let write_txn = self.db.begin_write()?;
+        write_txn.open_table(META_TABLE)?.remove(INDEX_STALE_KEY)?;
+        write_txn.commit()?;
+
+        println!(
+            "[storage] search index rebuilt: resources={} events={}",
+            report.resources_indexed, report.events_indexed
+        );
+
+        Ok(report)
+    }
+
+    /// Replace every document in the index by one per resource and event in redb, and commit.
+    fn rebuild_search_documents(
+        &self,
+        writer: &mut IndexWriter,
+    ) -> Result<ReindexReport, StorageError> {
+        let mut report = ReindexReport::default();
+        writer.delete_all_documents()?;
+
+        let read_txn = self.db.begin_read()?;
+
+        let events = read_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
+        for item in events.iter()? {
+            let (key, value) = item?;
+            let rec: EventRecord = decode(key.value(), value.value())?;
+            let event = rec.into_cloud_event(key.value())?;
+            writer.add_document(self.event_document(&event))?;
+            report.events_indexed += 1;
+        }
+
+        let resources = read_txn.open_table(RESOURCES_TABLE)?;
+        let by_resource = read_txn.open_multimap_table(EVENTS_BY_RESOURCE_TABLE)?;
+        for item in resources.iter()? {
+            let (key, value) = item?;
+            let rec: ResourceRecord = decode(key.value(), value.value())?;
+            let data = rec.json_data()?;
+            // Same parent and time as on live updates, taken from the last event that changed it
+            let last_seq = by_resource.get(rec.id.as_str())?.last().transpose()?;
+            let last_event = match last_seq {
+                Some(seq) => match events.get(seq.value())? {
+                    Some(bytes) => {
+                        let event_rec: EventRecord = decode(seq.value(), bytes.value())?;
+                        Some(event_rec.into_cloud_event(seq.value())?)
+                    }
+                    None => None,
+                },
+                None => None,
+            };
+            let issue_id = last_event
+                .as_ref()
+                .and_then(|event| projection::parent_issue_id(event, &rec.id));
+            let timestamp = match &last_event {
+                Some(event) => resource_time(event, &rec.updated_at),
+                None => rec.updated_at.clone(),
+            };
+
+            // Resources replace their earlier document, as on live updates
+            writer.delete_term(Term::from_field_text(self.resource_id_field, &rec.id));
+            writer.add_document(self.resource_document(
+                &rec.id,
+                &rec.resource_type,
+                &data,
+                &timestamp,
+                issue_id.as_deref(),
+            ))?;
+            report.resources_indexed += 1;
+        }
+
+        writer.commit()?;
+        Ok(report)
+    }
+
+    /// Compare the resources and events stored in redb with the documents in the search index.
+    ///
+    /// Pending index writes are committed first, so only documents that were really lost
+    /// (or never removed) show up as drift.
+    pub async fn check_index(&self) -> Result<IndexCheckReport, StorageError> {
+        self.flush_index().await?;
+
+        let mut db_ids = BTreeSet::new();
         {
-            let mut table = write_txn.open_table(RESOURCES_TABLE)?;
-            table.remove(id)?;
+            let read_txn = self.db.begin_read()?;
+            let resources = read_txn.open_table(RESOURCES_TABLE)?;
+            for item in resources.iter()? {
+                let (key, _value) = item?;
+                db_ids.insert(IndexedDoc::resource(key.value()));
+            }
+            let events_by_id = read_txn.open_table(EVENTS_BY_ID_TABLE)?;
+            for item in events_by_id.iter()? {
+                let (key, _value) = item?;
+                db_ids.insert(IndexedDoc::event(key.value()));
+            }
+        }
+
+        let reader = self
+            .search_index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let mut index_ids = BTreeSet::new();
+        for doc_address in searcher.search(&AllQuery, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let text = |field: Field| doc.get_first(field).and_then(|v| v.as_str());
+            if let Some(id) = text(self.resource_id_field) {
+                index_ids.insert(IndexedDoc::resource(id));
+            } else if let Some(id) = text(self.id_field) {
+                index_ids.insert(IndexedDoc::event(id));
+            }
+        }
+
+        Ok(IndexCheckReport {
+            db_ids: db_ids.len(),
+            index_ids: index_ids.len(),
+            missing_from_index: db_ids.difference(&index_ids).cloned().collect(),
+            missing_from_db: index_ids.difference(&db_ids).cloned().collect(),
+        })
+    }
+
+    /// Get a resource by ID
+    pub async fn get_resource(&self, id: &str) -> Result<Option<JsonValue>, StorageError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(RESOURCES_TABLE)?;
+
+        let result = table.get(id)?;
+
+        match result {
+            Some(bytes) => {
+                let rec: ResourceRecord = decode(id, bytes.value())?;
+                let data = rec.json_data()?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Delete a resource by storing a JSONCommit with `deleted: true`, so the deletion is in
+    /// the event log like every other change. The commit names the schema and subject of the
+    /// resource's last event. Returns the stored event, with its sequence, and the state it
+    /// left. Fails with [`StorageError::NotFound`] if the resource does not exist.
+    pub async fn delete_resource(
+        &self,
+        id: &str,
+    ) -> Result<(CloudEvent, CommittedState), StorageError> {
+        if self.get_resource(id).await?.is_none() {
+            return Err(StorageError::NotFound(format!("resource {}", id)));
         }
+        let last = self.resource_history(id).await?.pop();
+        let schema = last
+            .as_ref()
+            .filter(|event| projection::is_json_commit(event))
+            .and_then(|event| event.data.as_ref()?.get("schema")?.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut event = CloudEvent {
+            specversion: "1.0".to_string(),
+            id: uuid::Uuid::now_v7().to_string(),
+            source: DELETE_SOURCE.to_string(),
+            subject: last
+                .and_then(|event| event.subject)
+                .or_else(|| Some(id.to_string())),
+            event_type: "json.commit".to_string(),
+            time: Some(now.clone()),
+            datacontenttype: Some("application/json".to_string()),
+            dataschema: None,
+            dataref: None,
+            sequence: None,
+            sequencetype: None,
+            data: Some(serde_json::json!({
+                "schema": schema,
+                "resource_id": id,
+                "timestamp": now,
+                "deleted": true
+            })),
+        };
+        let (outcome, committed) = self.store_event_committed(&event).await?;
+        event.sequence = Some(outcome.sequence().to_string());
+        Ok((event, committed))
+    }
+
+    /// Remove the resource's document from the search index and commit, so deleted
+    /// resources disappear from search right away.
+    async fn remove_from_index(&self, id: &str) -> Result<(), StorageError> {
+        self.queue_index_op(IndexOp::Delete(id.to_string())).await;
+        self.flush_index().await
+    }
+
+    /// Store a push subscription, replacing any earlier one for the same endpoint (a browser
+    /// that re-subscribes gets new keys). Returns `true` if the endpoint was not known yet.
+    pub async fn put_push_subscription(
+        &self,
+        subscription: &PushSubscription,
+    ) -> Result<bool, StorageError> {
+        let json = serde_json::to_string(subscription)?;
+        let write_txn = self.db.begin_write()?;
+        let created = {
+            let mut table = write_txn.open_table(PUSH_SUBSCRIPTIONS_TABLE)?;
+            let previous = table.insert(subscription.endpoint.as_str(), json.as_str())?;
+            previous.is_none()
+        };
         write_txn.commit()?;
+        Ok(created)
+    }
 
-        // Remove from search index
-        let mut writer = self.search_writer.write().await;
-        writer.delete_term(Term::from_field_text(self.id_field, id));
-        writer.commit()?;
+    /// Store a push subscription like [`Storage::put_push_subscription`], but keep the filters
+    /// of the subscription already stored for its endpoint. Returns `true` if the endpoint was
+    /// not known yet (it gets the filters of `subscription` then).
+    pub async fn renew_push_subscription(
+        &self,
+        subscription: &PushSubscription,
+    ) -> Result<bool, StorageError> {
+        let endpoint = subscription.endpoint.as_str();
+        let write_txn = self.db.begin_write()?;
+        let created = {
+            let mut table = write_txn.open_table(PUSH_SUBSCRIPTIONS_TABLE)?;
+            let previous = table.get(endpoint)?.map(|json| json.value().to_string());
+            let mut renewed = subscription.clone();
+            if let Some(previous) = &previous {
+                let previous: PushSubscription = decode_json(endpoint, previous)?;
+                renewed.filters = previous.filters;
+            }
+            let json = serde_json::to_string(&renewed)?;
+            table.insert(endpoint, json.as_str())?;
+            previous.is_none()
+        };
+        write_txn.commit()?;
+        Ok(created)
+    }
 
-        Ok(())
+    /// Remove the push subscription for `endpoint` and its delivery statistics. Returns
+    /// `false` if there was none.
+    pub async fn delete_push_subscription(&self, endpoint: &str) -> Result<bool, StorageError> {
+        let write_txn = self.db.begin_write()?;
+        let removed = {
+            let mut table = write_txn.open_table(PUSH_SUBSCRIPTIONS_TABLE)?;
+            let previous = table.remove(endpoint)?;
+            write_txn
+                .open_table(PUSH_DELIVERY_STATS_TABLE)?
+                .remove(endpoint)?;
+            previous.is_some()
+        };
+        write_txn.commit()?;
+        Ok(removed)
     }
 
-    // Note: indexing is performed asynchronously by background tasks and commits are batched periodically.
+    /// All push subscriptions, ordered by endpoint.
+    pub async fn list_push_subscriptions(&self) -> Result<Vec<PushSubscription>, StorageError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(PUSH_SUBSCRIPTIONS_TABLE)?;
+        let mut subscriptions = Vec::new();
+        for item in table.iter()? {
+            let (key, value) = item?;
+            subscriptions.push(decode_json(key.value(), value.value())?);
+        }
+        Ok(subscriptions)
+    }
 
-    /// Search using Tantivy
-    pub async fn search(
+    /// Update the delivery statistics of the subscription for `endpoint`. Does nothing (and
+    /// returns `false`) when the endpoint is no longer subscribed.
+    pub async fn update_push_delivery_stats(
         &self,
-        query_str: &str,
-        limit: usize,
-    ) -> Result<Vec<SearchResult>, Box<dyn std::error::Error>> {
+        endpoint: &str,
+        update: impl FnOnce(&mut PushDeliveryStats),
+    ) -> Result<bool, StorageError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            if write_txn
+                .open_table(PUSH_SUBSCRIPTIONS_TABLE)?
+                .get(endpoint)?
+                .is_none()
+            {
+                return Ok(false);
+            }
+            let mut table = write_txn.open_table(PUSH_DELIVERY_STATS_TABLE)?;
+            let mut stats: PushDeliveryStats = match table.get(endpoint)? {
+                Some(json) => decode_json(endpoint, json.value())?,
+                None => PushDeliveryStats::default(),
+            };
+            update(&mut stats);
+            let json = serde_json::to_string(&stats)?;
+            table.insert(endpoint, json.as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(true)
+    }
+
+    /// Delivery statistics of every push subscription, ordered by endpoint. Subscriptions
+    /// that were never notified have zeroed statistics.
+    pub async fn list_push_delivery_stats(
+        &self,
+    ) -> Result<Vec<(String, PushDeliveryStats)>, StorageError> {
+        let read_txn = self.db.begin_read()?;
+        let subscriptions = read_txn.open_table(PUSH_SUBSCRIPTIONS_TABLE)?;
+        let stats = read_txn.open_table(PUSH_DELIVERY_STATS_TABLE)?;
+        let mut list = Vec::new();
+        for item in subscriptions.iter()? {
+            let (key, _) = item?;
+            let endpoint = key.value();
+            let entry = match stats.get(endpoint)? {
+                Some(json) => decode_json(endpoint, json.value())?,
+                None => PushDeliveryStats::default(),
+            };
+            list.push((endpoint.to_string(), entry));
+        }
+        Ok(list)
+    }
+
+    /// Search using Tantivy.
+    ///
+    /// `query.q` uses the Tantivy query syntax over `content`, `title` and `description` and
+    /// may scope terms to a field (`title:paspoort`, `assignee:alice@gemeente.nl`). An empty
+    /// query matches every document. Hits are ordered by score and paged with `offset`/`limit`;
+    /// `total` and the facet counts cover all matches, not just the returned page.
+    pub async fn search(&self, query: &SearchQuery) -> Result<SearchResults, StorageError> {
         let reader = self
             .search_index
             .reader_builder()
@@ -478,41 +1398,244 @@ impl Storage {
 
         let searcher = reader.searcher();
 
-        let query_parser = QueryParser::for_index(&self.search_index, vec![self.content_field]);
-        let query = query_parser.parse_query(query_str)?;
+        if query.fuzziness > MAX_FUZZINESS {
+            return Err(StorageError::InvalidQuery(format!(
+                "fuzziness must be at most {}, got {}",
+                MAX_FUZZINESS, query.fuzziness
+            )));
+        }
+
+        let text_fields = [self.content_field, self.title_field, self.description_field];
+        let query_parser = QueryParser::for_index(&self.search_index, text_fields.to_vec());
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        if query.q.trim().is_empty() {
+            clauses.push((Occur::Must, Box::new(AllQuery)));
+        } else if query.fuzziness == 0 {
+            clauses.push((Occur::Must, query_parser.parse_query(&query.q)?));
+        } else {
+            // Exact matches keep their relevance score and highlighting; the fuzzy variant
+            // (constant score) adds near misses. Whole words only: prefix matching would let
+            // short words hit every longer word starting with them, so completing half-typed
+            // words is left to `suggest`.
+            let mut fuzzy_parser = query_parser.clone();
+            for field in text_fields {
+                fuzzy_parser.set_field_fuzzy(field, false, query.fuzziness, true);
+            }
+            let either = BooleanQuery::new(vec![
+                (Occur::Should, query_parser.parse_query(&query.q)?),
+                (Occur::Should, fuzzy_parser.parse_query(&query.q)?),
+            ]);
+            clauses.push((Occur::Must, Box::new(either)));
+        }
+        for (field, value) in [
+            (self.type_field, &query.doc_type),
+            (self.status_field, &query.status),
+        ] {
+            if let Some(value) = value {
+                clauses.push((
+                    Occur::Must,
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(field, value),
+                        IndexRecordOption::Basic,
+                    )),
+                ));
+            }
+        }
+        if query.from.is_some() || query.to.is_some() {
+            let bound = |time: Option<chrono::DateTime<chrono::Utc>>| match time {
+                Some(t) => Bound::Included(tantivy::DateTime::from_timestamp_secs(t.timestamp())),
+                None => Bound::Unbounded,
+            };
+            clauses.push((
+                Occur::Must,
+                Box::new(RangeQuery::new_date_bounds(
+                    "timestamp".to_string(),
+                    bound(query.from),
+                    bound(query.to),
+                )),
+            ));
+        }
+        let search_query = BooleanQuery::new(clauses);
+
+        let mut facet_collector = FacetCollector::for_field("facet");
+        facet_collector.add_facet("/status");
+        facet_collector.add_facet("/type");
 
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let top_collector = TopDocs::with_limit(query.limit).and_offset(query.offset);
+        let (top_docs, total, facet_counts) = match query.sort {
+            SearchSort::Relevance => {
+                searcher.search(&search_query, &(top_collector, Count, facet_collector))?
+            }
+            SearchSort::Recent => {
+                // Newest first; documents with the same timestamp keep relevance order
+                let by_time = top_collector.tweak_score(|segment: &SegmentReader| {
+                    let timestamps = segment.fast_fields().date("timestamp").ok();
+                    move |doc: DocId, score: Score| {
+                        let secs = timestamps
+                            .as_ref()
+                            .and_then(|column| column.first(doc))
+                            .map(|t| t.into_timestamp_secs())
+                            .unwrap_or(i64::MIN);
+                        (secs, score)
+                    }
+                });
+                let (top_docs, total, facet_counts) =
+                    searcher.search(&search_query, &(by_time, Count, facet_collector))?;
+                let top_docs = top_docs
+                    .into_iter()
+                    .map(|((_secs, score), doc_address)| (score, doc_address))
+                    .collect();
+                (top_docs, total, facet_counts)
+            }
+        };
 
-        let mut results = Vec::new();
-        for (_score, doc_address) in top_docs {
+        // Highlight the most readable field that matched: description, then title, then the
+        // raw content blob.
+        let snippet_generators = [self.description_field, self.title_field, self.content_field]
+            .into_iter()
+            .map(|field| SnippetGenerator::create(&searcher, &search_query, field))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut hits = Vec::new();
+        for (score, doc_address) in top_docs {
             let retrieved_doc: tantivy::TantivyDocument = searcher.doc(doc_address)?;
+            let text = |field: Field| {
+                retrieved_doc
+                    .get_first(field)
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+            };
 
-            let id = retrieved_doc
-                .get_first(self.id_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            let doc_type = retrieved_doc
-                .get_first(self.type_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            let content = retrieved_doc
-                .get_first(self.content_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            results.push(SearchResult {
-                id,
-                doc_type,
-                content,
+            let snippet = snippet_generators
+                .iter()
+                .map(|generator| generator.snippet_from_doc(&retrieved_doc))
+                .find(|snippet| !snippet.highlighted().is_empty())
+                .map(|snippet| snippet.to_html());
+
+            hits.push(SearchResult {
+                id: text(self.id_field).unwrap_or_default(),
+                doc_type: text(self.type_field).unwrap_or_default(),
+                content: text(self.content_field).unwrap_or_default(),
+                title: text(self.title_field),
+                status: text(self.status_field),
+                score,
+                snippet,
+                issue_id: text(self.issue_id_field),
+                timestamp: retrieved_doc
+                    .get_first(self.timestamp_field)
+                    .and_then(|v| v.as_datetime())
+                    .and_then(|t| chrono::DateTime::from_timestamp(t.into_timestamp_secs(), 0))
+                    .map(|t| t.to_rfc3339()),
             });
         }
 
-        Ok(results)
+        let mut facets = BTreeMap::new();
+        for name in ["status", "type"] {
+            let counts: BTreeMap<String, u64> = facet_counts
+                .get(&format!("/{}", name))
+                .filter_map(|(facet, count)| {
+                    facet
+                        .to_path()
+                        .last()
+                        .map(|value| (value.to_string(), count))
+                })
+                .collect();
+            facets.insert(name.to_string(), counts);
+        }
+
+        let next_offset = Some(query.offset + hits.len()).filter(|next| *next < total);
+
+        Ok(SearchResults {
+            hits,
+            total,
+            next_offset,
+            facets,
+        })
+    }
+
+    /// Titles starting with `prefix`, for search-as-you-type.
+    ///
+    /// Every word but the last must match a title word; the last word matches as a prefix.
+    /// Words are stemmed like the indexed titles, so "aanvragen" also completes "aanvraag".
+    pub async fn suggest(
+        &self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<Suggestion>, StorageError> {
+        let mut analyzer = self
+            .search_index
+            .tokenizers()
+            .get(DUTCH_TOKENIZER)
+            .ok_or_else(|| StorageError::Index("Dutch analyzer not registered".to_string()))?;
+        let mut words = Vec::new();
+        analyzer
+            .token_stream(prefix)
+            .process(&mut |token| words.push(token.text.clone()));
+
+        let Some(last) = words.pop() else {
+            return Ok(Vec::new());
+        };
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = words
+            .iter()
+            .map(|word| -> (Occur, Box<dyn Query>) {
+                (
+                    Occur::Must,
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(self.title_field, word),
+                        IndexRecordOption::Basic,
+                    )),
+                )
+            })
+            .collect();
+        clauses.push((
+            Occur::Must,
+            Box::new(FuzzyTermQuery::new_prefix(
+                Term::from_field_text(self.title_field, &last),
+                0,
+                true,
+            )),
+        ));
+
+        let reader = self
+            .search_index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let top_docs = searcher.search(
+            &BooleanQuery::new(clauses),
+            &TopDocs::with_limit(limit.saturating_mul(4)),
+        )?;
+
+        let mut suggestions: Vec<Suggestion> = Vec::new();
+        for (_score, doc_address) in top_docs {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let Some(title) = doc.get_first(self.title_field).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            // Several resources can share a title; suggest it once
+            if suggestions.iter().any(|s| s.title == title) {
+                continue;
+            }
+            suggestions.push(Suggestion {
+                id: doc
+                    .get_first(self.id_field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                doc_type: doc
+                    .get_first(self.type_field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                title: title.to_string(),
+            });
+            if suggestions.len() >= limit {
+                break;
+            }
+        }
+
+        Ok(suggestions)
     }
 
     /// Get all resources (paginated)
@@ -520,28 +1643,24 @@ impl Storage {
         &self,
         offset: usize,
         limit: usize,
-    ) -> Result<Vec<(String, JsonValue)>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<(String, JsonValue)>, StorageError> {
         let mut results = Vec::new();
 
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(RESOURCES_TABLE)?;
 
-        let mut count = 0;
         let iter = table.iter()?;
 
-        for item in iter {
+        for item in iter.skip(offset) {
             let (key, value) = item?;
 
-            if count >= offset {
-                let rec: ResourceRecord = bincode::deserialize(value.value())?;
-                let data: JsonValue = serde_json::from_str(&rec.data)?;
-                results.push((key.value().to_string(), data));
+            let rec: ResourceRecord = decode(key.value(), value.value())?;
+            let data = rec.json_data()?;
+            results.push((key.value().to_string(), data));
 
-                if results.len() >= limit {
-                    break;
-                }
+            if results.len() >= limit {
+                break;
             }
-            count += 1;
         }
 
         Ok(results)
@@ -556,14 +1675,14 @@ impl Storage {
         &self,
         after_seq: Option<String>,
         limit: usize,
-    ) -> Result<Vec<CloudEvent>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<CloudEvent>, StorageError> {
         // Read events by sequence lexicographic order from EVENTS_BY_SEQ_TABLE (ensures server processing order).
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
 
         let mut results: Vec<CloudEvent> = Vec::new();
 
-        let mut iter = table.iter()?;
+        let iter = table.iter()?;
 
         for item in iter {
             let (key, value) = item?;
@@ -575,25 +1694,8 @@ impl Storage {
                 }
             }
 
-            let rec: EventRecord = bincode::deserialize(value.value())?;
-            let data: Option<JsonValue> = serde_json::from_str(&rec.data)?;
-
-            let event = CloudEvent {
-                specversion: "1.0".to_string(),
-                id: rec.id,
-                source: rec.source,
-                subject: rec.subject,
-                event_type: rec.event_type,
-                time: rec.time,
-                datacontenttype: Some("application/json".to_string()),
-                dataschema: None,
-                dataref: None,
-                sequence: rec.sequence,
-                sequencetype: None,
-                data,
-            };
-
-            results.push(event);
+            let rec: EventRecord = decode(key.value(), value.value())?;
+            results.push(rec.into_cloud_event(key.value())?);
             if results.len() >= limit {
                 break;
             }
@@ -609,7 +1711,7 @@ impl Storage {
         &self,
         offset: usize,
         limit: usize,
-    ) -> Result<Vec<CloudEvent>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<CloudEvent>, StorageError> {
         // If offset is zero, simply return first `limit` events
         if offset == 0 {
             return self.list_events_after(None, limit).await;
@@ -618,7 +1720,7 @@ impl Storage {
         // Otherwise, we need to skip `offset` keys - iterate and find the key at position `offset - 1`
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(EVENTS_BY_SEQ_TABLE)?;
-        let mut iter = table.iter()?;
+        let iter = table.iter()?;
 
         let mut seq_to_start: Option<String> = None;
         for (i, item) in iter.enumerate() {
@@ -630,17 +1732,387 @@ impl Storage {
         }
 
         // If we found the sequence key at offset-1, start after it; otherwise start from beginning
-        let after_seq = seq_to_start.map(|s| s);
-        self.list_events_after(after_seq, limit).await
+        self.list_events_after(seq_to_start, limit).await
+    }
+}
+
+/// When queued search index changes are committed (made searchable).
+#[derive(Debug, Clone)]
+pub struct IndexConfig {
+    /// Commit pending changes at least this often
+    pub commit_interval: std::time::Duration,
+    /// Commit as soon as this many changes are pending
+    pub commit_threshold: usize,
+    /// Number of changes that can wait for the indexer before writers are held up
+    pub queue_capacity: usize,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            commit_interval: std::time::Duration::from_secs(10),
+            commit_threshold: 1000,
+            queue_capacity: 10_000,
+        }
+    }
+}
+
+/// Change queued for the background indexer.
+enum IndexOp {
+    /// Add a document, first removing the document of the resource with the `replace` id
+    Add {
+        doc: TantivyDocument,
+        replace: Option<String>,
+    },
+    /// Remove the document of the resource with this id
+    Delete(String),
+    /// Commit pending changes and report the result
+    Flush(oneshot::Sender<Result<(), String>>),
+}
+
+/// Apply queued index changes in order and commit them by interval, by threshold and on
+/// flush. Pending changes are committed when the queue closes (the storage is dropped).
+async fn run_indexer(
+    mut rx: mpsc::Receiver<IndexOp>,
+    writer: Arc<RwLock<IndexWriter>>,
+    resource_id_field: Field,
+    config: IndexConfig,
+    commits: watch::Sender<u64>,
+) {
+    let mut pending = 0usize;
+    let mut ticker = tokio::time::interval_at(
+        tokio::time::Instant::now() + config.commit_interval,
+        config.commit_interval,
+    );
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let op = tokio::select! {
+            op = rx.recv() => op,
+            _ = ticker.tick() => {
+                if pending > 0 {
+                    let _ = commit_index(&writer, &commits, &mut pending, "periodic").await;
+                }
+                continue;
+            }
+        };
+
+        match op {
+            Some(IndexOp::Add { doc, replace }) => {
+                let writer = writer.read().await;
+                if let Some(id) = replace {
+                    writer.delete_term(Term::from_field_text(resource_id_field, &id));
+                }
+                match writer.add_document(doc) {
+                    Ok(_) => pending += 1,
+                    Err(e) => eprintln!("[storage][bg] failed adding document: {}", e),
+                }
+            }
+            Some(IndexOp::Delete(id)) => {
+                writer
+                    .read()
+                    .await
+                    .delete_term(Term::from_field_text(resource_id_field, &id));
+                pending += 1;
+            }
+            Some(IndexOp::Flush(reply)) => {
+                let result = commit_index(&writer, &commits, &mut pending, "flush").await;
+                let _ = reply.send(result);
+                continue;
+            }
+            None => {
+                let _ = commit_index(&writer, &commits, &mut pending, "final").await;
+                return;
+            }
+        }
+
+        if pending >= config.commit_threshold {
+            let _ = commit_index(&writer, &commits, &mut pending, "threshold").await;
+        }
+    }
+}
+
+/// Commit the index writer if anything is pending.
+async fn commit_index(
+    writer: &RwLock<IndexWriter>,
+    commits: &watch::Sender<u64>,
+    pending: &mut usize,
+    reason: &str,
+) -> Result<(), String> {
+    if *pending == 0 {
+        return Ok(());
+    }
+    match writer.write().await.commit() {
+        Ok(_) => {
+            println!(
+                "[storage][bg] {} commit completed ({} changes)",
+                reason, pending
+            );
+            *pending = 0;
+            commits.send_modify(|n| *n += 1);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("[storage][bg] {} commit failed: {}", reason, e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Decode a bincode record stored under `key`.
+fn decode<T: DeserializeOwned>(key: &str, bytes: &[u8]) -> Result<T, StorageError> {
+    bincode::deserialize(bytes).map_err(|e| StorageError::CorruptRecord {
+        key: key.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// Parse the JSON payload of a record stored under `key`.
+fn decode_json<T: DeserializeOwned>(key: &str, json: &str) -> Result<T, StorageError> {
+    serde_json::from_str(json).map_err(|e| StorageError::CorruptRecord {
+        key: key.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// Error for an event whose commit cannot be applied to the projection, naming the
+/// offending attribute of the commit when it can be located.
+fn invalid_event(event: &CloudEvent, e: serde_json::Error) -> StorageError {
+    let field = event
+        .data
+        .as_ref()
+        .and_then(|data| serde_path_to_error::deserialize::<_, JSONCommit>(data).err())
+        .and_then(|e| error_field(&e.path().to_string(), &e.inner().to_string()))
+        .map(|field| format!("data.{}", field));
+    StorageError::InvalidEvent {
+        event_id: event.id.clone(),
+        field,
+        message: e.to_string(),
+    }
+}
+
+/// Reject a JSONCommit whose resulting resource does not match the schema it names.
+fn check_resource_schema(event: &CloudEvent, resource: &JsonValue) -> Result<(), StorageError> {
+    if !projection::is_json_commit(event) {
+        return Ok(());
+    }
+    let Some(schema) = event
+        .data
+        .as_ref()
+        .and_then(|d| d.get("schema"))
+        .and_then(|s| s.as_str())
+    else {
+        return Ok(());
+    };
+
+    let violations = validation::validate_resource(schema, resource);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(StorageError::SchemaViolations {
+        event_id: event.id.clone(),
+        schema: validation::schema_name(schema).to_string(),
+        violations,
+    })
+}
+
+/// Analyzer for Dutch free text: split on non-alphanumerics, lowercase, stem.
+fn dutch_analyzer() -> TextAnalyzer {
+    TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+        .filter(Stemmer::new(Language::Dutch))
+        .build()
+}
+
+/// The time a resource is indexed with after `event` changed it: the event's own `time`, so
+/// date filters and `sort=recent` follow when things happened rather than when they were
+/// stored, or `updated_at` when the event has no valid time.
+fn resource_time(event: &CloudEvent, updated_at: &str) -> String {
+    event
+        .time
+        .as_deref()
+        .filter(|time| chrono::DateTime::parse_from_rfc3339(time).is_ok())
+        .unwrap_or(updated_at)
+        .to_string()
+}
+
+/// The resource `change` left behind and the resource named by the event's subject, read in
+/// the write transaction that applied the event.
+fn committed_state(
+    write_txn: &WriteTransaction,
+    event: &CloudEvent,
+    change: Option<&ResourceChange>,
+) -> Result<CommittedState, StorageError> {
+    let (id, resource) = match change {
+        Some(ResourceChange::Upsert { id, data, .. }) => (id, Some(data.clone())),
+        Some(ResourceChange::Delete { id }) => (id, None),
+        None => return Ok(CommittedState::default()),
+    };
+    let subject = match event.subject.as_deref() {
+        Some(subject) if subject != id => {
+            let resources = write_txn.open_table(RESOURCES_TABLE)?;
+            let record = resources.get(subject)?;
+            match record {
+                Some(bytes) => Some(decode::<ResourceRecord>(subject, bytes.value())?.json_data()?),
+                None => None,
+            }
+        }
+        _ => resource.clone(),
+    };
+    Ok(CommittedState { resource, subject })
+}
+
+/// Apply `event` to the resources projection inside an open write transaction and record it
+/// in the per-resource history index. Nothing is written if the event cannot be applied.
+fn project_event(
+    write_txn: &WriteTransaction,
+    event: &CloudEvent,
+    seq_key: &str,
+    updated_at: &str,
+) -> Result<Option<ResourceChange>, StorageError> {
+    let resource_id = match projection::resource_id(event).map_err(|e| invalid_event(event, e))? {
+        Some(resource_id) => resource_id,
+        None => return Ok(None),
+    };
+
+    let mut resources = write_txn.open_table(RESOURCES_TABLE)?;
+    let existing = match resources.get(resource_id.as_str())? {
+        Some(bytes) => {
+            let rec: ResourceRecord = decode(&resource_id, bytes.value())?;
+            Some(rec.json_data()?)
+        }
+        None => None,
+    };
+    let change = projection::apply_event(event, existing).map_err(|e| invalid_event(event, e))?;
+
+    let serialized = match &change {
+        Some(ResourceChange::Upsert {
+            id,
+            resource_type,
+            data,
+        }) => Some(bincode::serialize(&ResourceRecord {
+            id: id.clone(),
+            resource_type: resource_type.clone(),
+            data: serde_json::to_string(data)?,
+            updated_at: updated_at.to_string(),
+        })?),
+        _ => None,
+    };
+
+    let mut by_resource = write_txn.open_multimap_table(EVENTS_BY_RESOURCE_TABLE)?;
+    by_resource.insert(resource_id.as_str(), seq_key)?;
+
+    match (&change, serialized) {
+        (Some(ResourceChange::Upsert { id, .. }), Some(serialized)) => {
+            resources.insert(id.as_str(), serialized.as_slice())?;
+        }
+        (Some(ResourceChange::Delete { id }), _) => {
+            resources.remove(id.as_str())?;
+        }
+        _ => {}
+    }
+
+    Ok(change)
+}
+
+/// Parameters for [`Storage::search`].
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: usize,
+    /// Number of hits to skip, see [`SearchResults::next_offset`]
+    pub offset: usize,
+    /// Only match documents of this type (resource type or CloudEvent type)
+    pub doc_type: Option<String>,
+    /// Only match resources with this status
+    pub status: Option<String>,
+    /// Maximum edit distance for fuzzy matching of free-text terms (0 disables it)
+    pub fuzziness: u8,
+    /// Only match documents timestamped at or after this instant
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only match documents timestamped at or before this instant
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    pub sort: SearchSort,
+}
+
+/// Order of [`Storage::search`] hits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchSort {
+    /// Best match first
+    #[default]
+    Relevance,
+    /// Most recently updated first
+    Recent,
+}
+
+impl SearchQuery {
+    pub fn new(q: &str, limit: usize) -> Self {
+        Self {
+            q: q.to_string(),
+            limit,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        Self {
+            q: String::new(),
+            limit: 10,
+            offset: 0,
+            doc_type: None,
+            status: None,
+            fuzziness: DEFAULT_FUZZINESS,
+            from: None,
+            to: None,
+            sort: SearchSort::Relevance,
+        }
     }
 }
 
+/// Autocomplete entry returned by [`Storage::suggest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub id: String,
+    pub doc_type: String,
+    pub title: String,
+}
+
 /// Search result structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub id: String,
     pub doc_type: String,
     pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Relevance score (higher is better)
+    pub score: f32,
+    /// HTML fragment of the best matching field, with query terms wrapped in `<b>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    /// Issue this comment, task or other timeline item belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue_id: Option<String>,
+    /// When the resource was last updated or the event happened (RFC 3339)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+/// Hits and facet counts returned by [`Storage::search`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchResult>,
+    /// Number of matching documents
+    pub total: usize,
+    /// Offset of the next page, `None` on the last page
+    pub next_offset: Option<usize>,
+    /// Matching documents per facet value, e.g. `{"status": {"open": 3}, "type": {"issue": 3}}`
+    pub facets: BTreeMap<String, BTreeMap<String, u64>>,
 }
 
 #[cfg(test)]
@@ -673,10 +2145,490 @@ mod tests {
         let retrieved = storage.get_event("test-event-1").await.unwrap();
         assert!(retrieved.is_some());
         assert_eq!(retrieved.unwrap().id, "test-event-1");
+
+        assert!(storage.get_event("missing").await.unwrap().is_none());
     }
 
     #[tokio::test]
-    async fn test_storage_resource_round_trip() {
+    async fn test_store_event_is_idempotent_on_source_and_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let event = CloudEvent {
+            specversion: "1.0".to_string(),
+            id: "retry-me".to_string(),
+            source: "producer-a".to_string(),
+            subject: None,
+            event_type: "test.event".to_string(),
+            time: None,
+            datacontenttype: None,
+            dataschema: None,
+            dataref: None,
+            sequence: None,
+            sequencetype: None,
+            data: Some(serde_json::json!({"n": 1})),
+        };
+
+        let first = storage.store_event(&event).await.unwrap();
+        assert!(matches!(first, StoreOutcome::Stored(_)));
+
+        let retry = storage.store_event(&event).await.unwrap();
+        assert_eq!(retry, StoreOutcome::Duplicate(first.sequence().to_string()));
+
+        // The same id from a different source is a different event.
+        let other_source = CloudEvent {
+            source: "producer-b".to_string(),
+            ..event.clone()
+        };
+        let other = storage.store_event(&other_source).await.unwrap();
+        assert!(matches!(other, StoreOutcome::Stored(_)));
+        assert_ne!(other.sequence(), first.sequence());
+
+        let events = storage.list_events_after(None, 10).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].sequence.as_deref(), Some(first.sequence()));
+    }
+
+    fn commit_event(id: &str, commit: JsonValue) -> CloudEvent {
+        CloudEvent {
+            specversion: "1.0".to_string(),
+            id: id.to_string(),
+            source: "test".to_string(),
+            subject: Some("issue-1".to_string()),
+            event_type: "json.commit".to_string(),
+            time: Some(chrono::Utc::now().to_rfc3339()),
+            datacontenttype: Some("application/json".to_string()),
+            dataschema: None,
+            dataref: None,
+            sequence: None,
+            sequencetype: None,
+            data: Some(commit),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_typed() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        assert!(matches!(
+            storage.delete_resource("missing").await,
+            Err(StorageError::NotFound(_))
+        ));
+        assert!(matches!(
+            storage.search(&SearchQuery::new("title:(open", 10)).await,
+            Err(StorageError::InvalidQuery(_))
+        ));
+
+        // A record that cannot be decoded is reported with its key
+        let write_txn = storage.db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(RESOURCES_TABLE).unwrap();
+            table.insert("broken", b"not bincode".as_slice()).unwrap();
+        }
+        write_txn.commit().unwrap();
+        assert!(matches!(
+            storage.get_resource("broken").await,
+            Err(StorageError::CorruptRecord { key, .. }) if key == "broken"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_commits_must_match_resource_schema() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        let issue = "http://localhost:8000/schemas/Issue";
+
+        let create = commit_event(
+            "create",
+            serde_json::json!({
+                "schema": issue,
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+
+        // The patched resource is validated, so every violation is reported at once
+        let patch = commit_event(
+            "patch",
+            serde_json::json!({
+                "schema": issue,
+                "resource_id": "issue-1",
+                "patch": {"title": null, "status": "afgesloten"}
+            }),
+        );
+        match storage.store_event(&patch).await {
+            Err(StorageError::SchemaViolations {
+                event_id,
+                schema,
+                violations,
+            }) => {
+                assert_eq!(event_id, "patch");
+                assert_eq!(schema, "Issue");
+                let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+                assert!(paths.contains(&"/title"), "{:?}", violations);
+                assert!(paths.contains(&"/status"), "{:?}", violations);
+            }
+            other => panic!("expected schema violations, got {:?}", other),
+        }
+
+        // The rejected commit leaves neither an event nor a change behind
+        assert_eq!(storage.list_events(0, 10).await.unwrap().len(), 1);
+        assert_eq!(
+            storage.get_resource("issue-1").await.unwrap().unwrap()["status"],
+            "open"
+        );
+
+        // Unpublished schemas and deletions are not validated
+        let custom = commit_event(
+            "custom",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/LLMAnalysis",
+                "resource_id": "analysis-1",
+                "resource_data": {"score": 3}
+            }),
+        );
+        storage.store_event(&custom).await.unwrap();
+        let delete = commit_event(
+            "delete",
+            serde_json::json!({"schema": issue, "resource_id": "issue-1", "deleted": true}),
+        );
+        storage.store_event(&delete).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_store_event_applies_projection_atomically() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        // A malformed commit is rejected without storing the event or burning a sequence.
+        let malformed = commit_event("bad", serde_json::json!({"resource_id": 1}));
+        assert!(matches!(
+            storage.store_event(&malformed).await,
+            Err(StorageError::InvalidEvent { event_id, field, .. })
+                if event_id == "bad" && field.as_deref() == Some("data.resource_id")
+        ));
+        assert!(storage.list_events(0, 10).await.unwrap().is_empty());
+
+        let create = commit_event(
+            "create",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        let outcome = storage.store_event(&create).await.unwrap();
+        assert_eq!(outcome, StoreOutcome::Stored(sequence_key(1)));
+        assert_eq!(
+            storage.get_resource("issue-1").await.unwrap().unwrap()["status"],
+            "open"
+        );
+
+        let patch = commit_event(
+            "patch",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "patch": {"status": "closed"}
+            }),
+        );
+        storage.store_event(&patch).await.unwrap();
+        let issue = storage.get_resource("issue-1").await.unwrap().unwrap();
+        assert_eq!(issue["status"], "closed");
+        assert_eq!(issue["title"], "Paspoort");
+    }
+
+    #[tokio::test]
+    async fn test_delete_is_stored_when_index_update_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::new(temp_dir.path()).await.unwrap();
+        let issue = "http://localhost:8000/schemas/Issue";
+        let create = commit_event(
+            "create",
+            serde_json::json!({
+                "schema": issue,
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+        storage.flush_index().await.unwrap();
+
+        // Without an indexer the removal from the index fails after the commit
+        storage.indexer.abort();
+        let _ = (&mut storage.indexer).await;
+        let delete = commit_event(
+            "delete",
+            serde_json::json!({"schema": issue, "resource_id": "issue-1", "deleted": true}),
+        );
+        let outcome = storage.store_event(&delete).await.unwrap();
+        assert!(matches!(outcome, StoreOutcome::Stored(_)));
+        assert!(storage.get_resource("issue-1").await.unwrap().is_none());
+        let stale = |storage: &Storage| {
+            storage
+                .db
+                .begin_read()
+                .unwrap()
+                .open_table(META_TABLE)
+                .unwrap()
+                .get(INDEX_STALE_KEY)
+                .unwrap()
+                .is_some()
+        };
+        assert!(stale(&storage));
+        drop(storage);
+
+        // The next start rebuilds the index without the deleted issue
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        assert!(!stale(&storage));
+        assert!(storage.check_index().await.unwrap().is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_update_dropped_by_stopped_indexer_marks_index_stale() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::new(temp_dir.path()).await.unwrap();
+        storage.indexer.abort();
+        let _ = (&mut storage.indexer).await;
+
+        let create = commit_event(
+            "create",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+        let stale = storage
+            .db
+            .begin_read()
+            .unwrap()
+            .open_table(META_TABLE)
+            .unwrap()
+            .get(INDEX_STALE_KEY)
+            .unwrap()
+            .is_some();
+        assert!(stale);
+        drop(storage);
+
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        assert!(storage.check_index().await.unwrap().is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_resource_history_is_ordered_per_resource() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let commits = [
+            (
+                "create-1",
+                "issue-1",
+                serde_json::json!({"resource_data": {"title": "A", "status": "open"}}),
+            ),
+            (
+                "create-2",
+                "issue-2",
+                serde_json::json!({"resource_data": {"title": "B", "status": "open"}}),
+            ),
+            (
+                "patch-1",
+                "issue-1",
+                serde_json::json!({"patch": {"status": "closed"}}),
+            ),
+            ("delete-1", "issue-1", serde_json::json!({"deleted": true})),
+        ];
+        for (event_id, resource_id, fields) in commits {
+            let mut commit = serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": resource_id,
+            });
+            commit
+                .as_object_mut()
+                .unwrap()
+                .extend(fields.as_object().unwrap().clone());
+            storage
+                .store_event(&commit_event(event_id, commit))
+                .await
+                .unwrap();
+        }
+
+        let history = storage.resource_history("issue-1").await.unwrap();
+        let ids: Vec<&str> = history.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["create-1", "patch-1", "delete-1"]);
+
+        assert_eq!(storage.resource_history("issue-2").await.unwrap().len(), 1);
+        assert!(storage
+            .resource_history("missing")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resource_at_matches_live_projection() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let monday = chrono::DateTime::parse_from_rfc3339("2024-01-15T09:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let commits = [
+            (
+                0,
+                serde_json::json!({"resource_data": {"title": "Paspoort", "status": "open"}}),
+            ),
+            (1, serde_json::json!({"patch": {"status": "in_progress"}})),
+            (
+                2,
+                serde_json::json!({"patch": {"assignee": "alice@gemeente.nl"}}),
+            ),
+            (3, serde_json::json!({"patch": {"status": "closed"}})),
+        ];
+
+        let mut sequences = Vec::new();
+        for (day, fields) in commits {
+            let mut commit = serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+            });
+            commit
+                .as_object_mut()
+                .unwrap()
+                .extend(fields.as_object().unwrap().clone());
+            let mut event = commit_event(&format!("commit-{}", day), commit);
+            event.time = Some((monday + chrono::Duration::days(day)).to_rfc3339());
+            let outcome = storage.store_event(&event).await.unwrap();
+            sequences.push(outcome.sequence().to_string());
+        }
+
+        // Folding the whole history reproduces the live projection.
+        let live = storage.get_resource("issue-1").await.unwrap();
+        let latest = AsOf::Sequence(sequences[3].clone());
+        assert_eq!(storage.resource_at("issue-1", &latest).await.unwrap(), live);
+
+        let after_first = AsOf::Sequence(sequences[0].clone());
+        assert_eq!(
+            storage.resource_at("issue-1", &after_first).await.unwrap(),
+            Some(serde_json::json!({"title": "Paspoort", "status": "open"}))
+        );
+
+        // Tuesday noon: the status change has been applied, the assignment (Wednesday) not yet.
+        let tuesday_noon = AsOf::Time(monday + chrono::Duration::hours(27));
+        let state = storage
+            .resource_at("issue-1", &tuesday_noon)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state["status"], "in_progress");
+        assert!(state.get("assignee").is_none());
+
+        // Before the resource was created it did not exist.
+        let before = AsOf::Time(monday - chrono::Duration::days(1));
+        assert!(storage
+            .resource_at("issue-1", &before)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resource_at_time_uses_each_events_own_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let monday = chrono::DateTime::parse_from_rfc3339("2024-01-15T09:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        // The assignment arrives last but its producer's clock says Tuesday
+        let commits = [
+            (
+                0,
+                serde_json::json!({"resource_data": {"title": "Paspoort", "status": "open"}}),
+            ),
+            (2, serde_json::json!({"patch": {"status": "closed"}})),
+            (
+                1,
+                serde_json::json!({"patch": {"assignee": "alice@gemeente.nl"}}),
+            ),
+        ];
+        for (day, fields) in commits {
+            let mut commit = serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+            });
+            commit
+                .as_object_mut()
+                .unwrap()
+                .extend(fields.as_object().unwrap().clone());
+            let mut event = commit_event(&format!("commit-{}", day), commit);
+            event.time = Some((monday + chrono::Duration::days(day)).to_rfc3339());
+            storage.store_event(&event).await.unwrap();
+        }
+
+        let tuesday_noon = AsOf::Time(monday + chrono::Duration::hours(27));
+        let state = storage
+            .resource_at("issue-1", &tuesday_noon)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state["status"], "open");
+        assert_eq!(state["assignee"], "alice@gemeente.nl");
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_projection_replays_event_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let create = commit_event(
+            "create",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        let patch = commit_event(
+            "patch",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "patch": {"status": "closed"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+        storage.store_event(&patch).await.unwrap();
+        let live = storage.get_resource("issue-1").await.unwrap();
+
+        // Simulate a corrupted projection: a stray resource and a lost one.
+        storage
+            .store_resource("stray", "issue", &serde_json::json!({"title": "Stray"}))
+            .await
+            .unwrap();
+        let write_txn = storage.db.begin_write().unwrap();
+        write_txn
+            .open_table(RESOURCES_TABLE)
+            .unwrap()
+            .remove("issue-1")
+            .unwrap();
+        write_txn.commit().unwrap();
+
+        let report = storage.rebuild_projection().await.unwrap();
+        assert_eq!(report.events_replayed, 2);
+        assert_eq!(report.resources_written, 1);
+        assert!(report.failures.is_empty());
+
+        assert_eq!(storage.get_resource("issue-1").await.unwrap(), live);
+        assert!(storage.get_resource("stray").await.unwrap().is_none());
+        assert_eq!(storage.resource_history("issue-1").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_storage_resource_round_trip() {
         let temp_dir = TempDir::new().unwrap();
         let storage = Storage::new(temp_dir.path()).await.unwrap();
 
@@ -710,13 +2662,703 @@ mod tests {
             .await
             .unwrap();
 
-        // Give the index a moment to commit
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        storage.flush_index().await.unwrap();
 
-        let results = storage.search("critical", 10).await.unwrap();
+        let results = storage
+            .search(&SearchQuery::new("critical", 10))
+            .await
+            .unwrap()
+            .hits;
         assert!(!results.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_check_index_detects_drift_and_reindex_repairs_it() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let create = commit_event(
+            "create",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+
+        let report = storage.check_index().await.unwrap();
+        assert!(report.is_consistent(), "{:?}", report);
+        assert_eq!(report.db_ids, 2);
+
+        // Lose a document and leave a stale one behind.
+        {
+            let mut writer = storage.search_writer.write().await;
+            writer.delete_term(Term::from_field_text(storage.id_field, "issue-1"));
+            writer
+                .add_document(doc!(
+                    storage.id_field => "ghost",
+                    storage.content_field => "ghost",
+                ))
+                .unwrap();
+            writer.commit().unwrap();
+        }
+
+        let report = storage.check_index().await.unwrap();
+        assert_eq!(
+            report.missing_from_index,
+            vec![IndexedDoc::resource("issue-1")]
+        );
+        assert_eq!(report.missing_from_db, vec![IndexedDoc::event("ghost")]);
+
+        let reindexed = storage.reindex().await.unwrap();
+        assert_eq!(reindexed.resources_indexed, 1);
+        assert_eq!(reindexed.events_indexed, 1);
+        assert!(storage.check_index().await.unwrap().is_consistent());
+        assert!(!storage
+            .search(&SearchQuery::new("Paspoort", 10))
+            .await
+            .unwrap()
+            .hits
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_index_tells_resources_and_events_apart() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        // Event "7" creates resource "7"
+        let create = commit_event(
+            "7",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "7",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+        let report = storage.check_index().await.unwrap();
+        assert!(report.is_consistent(), "{:?}", report);
+        assert_eq!((report.db_ids, report.index_ids), (2, 2));
+
+        // Losing the resource document is drift, although an event with its id is indexed
+        {
+            let mut writer = storage.search_writer.write().await;
+            writer.delete_term(Term::from_field_text(storage.resource_id_field, "7"));
+            writer.commit().unwrap();
+        }
+        let report = storage.check_index().await.unwrap();
+        assert_eq!(report.missing_from_index, vec![IndexedDoc::resource("7")]);
+        assert!(report.missing_from_db.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_reindex_leaves_the_index_alone() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        storage
+            .store_resource(
+                "issue-1",
+                "issue",
+                &serde_json::json!({"title": "Paspoort", "status": "open"}),
+            )
+            .await
+            .unwrap();
+        // issue-1 is still queued: the rebuild commits it before it starts
+
+        // A corrupt record makes the rebuild fail halfway
+        {
+            let write_txn = storage.db.begin_write().unwrap();
+            write_txn
+                .open_table(RESOURCES_TABLE)
+                .unwrap()
+                .insert("issue-2", b"not bincode".as_slice())
+                .unwrap();
+            write_txn.commit().unwrap();
+        }
+        assert!(matches!(
+            storage.reindex().await,
+            Err(StorageError::CorruptRecord { key, .. }) if key == "issue-2"
+        ));
+
+        // The next commit must not apply the delete-all of the failed rebuild
+        storage
+            .store_resource(
+                "issue-3",
+                "issue",
+                &serde_json::json!({"title": "Rijbewijs"}),
+            )
+            .await
+            .unwrap();
+        storage.flush_index().await.unwrap();
+        assert_eq!(
+            storage
+                .search(&SearchQuery::new("Paspoort", 10))
+                .await
+                .unwrap()
+                .hits
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resource_updates_replace_search_document() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        for status in ["open", "pending", "closed"] {
+            storage
+                .store_resource(
+                    "issue-1",
+                    "issue",
+                    &serde_json::json!({"title": "Paspoort", "status": status}),
+                )
+                .await
+                .unwrap();
+        }
+        storage.flush_index().await.unwrap();
+
+        let results = storage
+            .search(&SearchQuery::new("Paspoort", 10))
+            .await
+            .unwrap()
+            .hits;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].content.contains("closed"));
+        assert!(storage
+            .search(&SearchQuery::new("pending", 10))
+            .await
+            .unwrap()
+            .hits
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_filters_fields_and_facets() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let resources = [
+            (
+                "issue-1",
+                "issue",
+                serde_json::json!({"title": "Paspoort aanvragen", "status": "open", "assignee": "alice@gemeente.nl"}),
+            ),
+            (
+                "issue-2",
+                "issue",
+                serde_json::json!({"title": "Kapvergunning", "description": "Paspoort kwijt", "status": "closed"}),
+            ),
+            (
+                "task-1",
+                "task",
+                serde_json::json!({"cta": "Paspoort controleren", "description": "", "url": "", "completed": false}),
+            ),
+        ];
+        for (id, resource_type, data) in &resources {
+            storage
+                .store_resource(id, resource_type, data)
+                .await
+                .unwrap();
+        }
+        storage.flush_index().await.unwrap();
+
+        let ids = |results: &SearchResults| {
+            let mut ids: Vec<String> = results.hits.iter().map(|h| h.id.clone()).collect();
+            ids.sort();
+            ids
+        };
+
+        let all = storage
+            .search(&SearchQuery::new("paspoort", 10))
+            .await
+            .unwrap();
+        assert_eq!(ids(&all), vec!["issue-1", "issue-2", "task-1"]);
+        assert_eq!(all.facets["status"]["open"], 2);
+        assert_eq!(all.facets["status"]["closed"], 1);
+        assert_eq!(all.facets["type"]["issue"], 2);
+
+        let titles = storage
+            .search(&SearchQuery::new("title:paspoort", 10))
+            .await
+            .unwrap();
+        assert_eq!(ids(&titles), vec!["issue-1", "task-1"]);
+
+        let open_issues = storage
+            .search(&SearchQuery {
+                doc_type: Some("issue".to_string()),
+                status: Some("open".to_string()),
+                ..SearchQuery::new("", 10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids(&open_issues), vec!["issue-1"]);
+        assert_eq!(
+            open_issues.hits[0].title.as_deref(),
+            Some("Paspoort aanvragen")
+        );
+
+        let assigned = storage
+            .search(&SearchQuery::new("assignee:alice@gemeente.nl", 10))
+            .await
+            .unwrap();
+        assert_eq!(ids(&assigned), vec!["issue-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_pages_scores_and_highlights() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        storage
+            .store_event(&commit_event(
+                "create-issue",
+                serde_json::json!({
+                    "schema": "http://localhost:8000/schemas/Issue",
+                    "resource_id": "1",
+                    "resource_data": {"title": "Paspoort", "status": "open"}
+                }),
+            ))
+            .await
+            .unwrap();
+        let mut comment = commit_event(
+            "create-comment",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Comment",
+                "resource_id": "comment-1",
+                "resource_data": {"content": "De pasfoto voor het paspoort ontbreekt nog"}
+            }),
+        );
+        comment.subject = Some("1".to_string());
+        storage.store_event(&comment).await.unwrap();
+        storage.flush_index().await.unwrap();
+
+        let query = SearchQuery {
+            doc_type: Some("comment".to_string()),
+            ..SearchQuery::new("pasfoto", 10)
+        };
+        let results = storage.search(&query).await.unwrap();
+        assert_eq!(results.total, 1);
+        let hit = &results.hits[0];
+        assert_eq!(hit.id, "comment-1");
+        assert_eq!(hit.issue_id.as_deref(), Some("1"));
+        assert!(hit.score > 0.0);
+        assert!(hit.snippet.as_deref().unwrap().contains("<b>pasfoto</b>"));
+
+        // Issue, comment and both commit events mention the passport
+        let first = storage
+            .search(&SearchQuery::new("paspoort", 3))
+            .await
+            .unwrap();
+        assert_eq!(first.total, 4);
+        assert_eq!(first.hits.len(), 3);
+        assert_eq!(first.next_offset, Some(3));
+        assert!(first.hits.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let second = storage
+            .search(&SearchQuery {
+                offset: 3,
+                ..SearchQuery::new("paspoort", 3)
+            })
+            .await
+            .unwrap();
+        assert_eq!(second.hits.len(), 1);
+        assert_eq!(second.next_offset, None);
+        assert!(first.hits.iter().all(|h| h.id != second.hits[0].id));
+    }
+
+    /// Index the municipal sample issues as resources `issue-1`, `issue-2`, ...
+    async fn store_issue_templates(storage: &Storage) {
+        for (i, (title, description, assignee)) in crate::issues::ISSUE_TEMPLATES.iter().enumerate()
+        {
+            let data = serde_json::json!({
+                "title": title,
+                "description": description,
+                "status": "open",
+                "assignee": assignee,
+            });
+            storage
+                .store_resource(&format!("issue-{}", i + 1), "issue", &data)
+                .await
+                .unwrap();
+        }
+        storage.flush_index().await.unwrap();
+    }
+
+    fn hit_titles(results: &SearchResults) -> Vec<String> {
+        results
+            .hits
+            .iter()
+            .filter_map(|h| h.title.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_is_fuzzy_and_stemmed_for_dutch() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        store_issue_templates(&storage).await;
+
+        // Typo within the default edit distance
+        let typo = storage
+            .search(&SearchQuery::new("paspoprt", 10))
+            .await
+            .unwrap();
+        assert!(hit_titles(&typo).contains(&"Nieuw paspoort aanvragen".to_string()));
+
+        let exact_only = storage
+            .search(&SearchQuery {
+                fuzziness: 0,
+                ..SearchQuery::new("paspoprt", 10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(exact_only.total, 0);
+
+        // Search matches whole words; half-typed words are completed by `suggest`
+        let partial = storage.search(&SearchQuery::new("pasp", 10)).await.unwrap();
+        assert_eq!(partial.total, 0);
+
+        // "vergunningen" and "vergunning" share a stem
+        assert!(storage
+            .search(&SearchQuery {
+                fuzziness: 0,
+                ..SearchQuery::new("description:vergunningen", 10)
+            })
+            .await
+            .unwrap()
+            .hits
+            .iter()
+            .any(|h| h.id == "issue-5"));
+
+        assert!(matches!(
+            storage
+                .search(&SearchQuery {
+                    fuzziness: 3,
+                    ..SearchQuery::new("paspoort", 10)
+                })
+                .await,
+            Err(StorageError::InvalidQuery(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_suggest_completes_titles() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        store_issue_templates(&storage).await;
+
+        let titles = |suggestions: Vec<Suggestion>| -> Vec<String> {
+            suggestions.into_iter().map(|s| s.title).collect()
+        };
+
+        assert_eq!(
+            titles(storage.suggest("pasp", 10).await.unwrap()),
+            vec!["Nieuw paspoort aanvragen"]
+        );
+        assert_eq!(
+            titles(storage.suggest("Kapverg", 10).await.unwrap()),
+            vec!["Kapvergunning boom"]
+        );
+        assert_eq!(
+            titles(storage.suggest("nieuw pasp", 10).await.unwrap()),
+            vec!["Nieuw paspoort aanvragen"]
+        );
+
+        let mut aanvragen = titles(storage.suggest("aanvragen", 10).await.unwrap());
+        aanvragen.sort();
+        assert_eq!(
+            aanvragen,
+            vec![
+                "Nieuw paspoort aanvragen",
+                "Parkeervergunning aanvraag",
+                "Uitkering aanvragen"
+            ]
+        );
+
+        assert!(storage.suggest("  ", 10).await.unwrap().is_empty());
+        assert_eq!(storage.suggest("a", 1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_by_date_range_and_recency() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        let day = |d: u32| {
+            chrono::NaiveDate::from_ymd_opt(2024, 1, d)
+                .unwrap()
+                .and_hms_opt(9, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        for (id, title, time) in [
+            ("old", "Paspoort verlopen", day(1)),
+            ("recent", "Paspoort kwijt", day(10)),
+            ("newest", "Paspoort paspoort aanvragen", day(14)),
+            ("other", "Rijbewijs", day(14)),
+        ] {
+            storage
+                .schedule_resource_index(
+                    id,
+                    "issue",
+                    &serde_json::json!({"title": title}),
+                    &time.to_rfc3339(),
+                    None,
+                )
+                .await;
+        }
+        storage.flush_index().await.unwrap();
+
+        let ids = |results: SearchResults| -> Vec<String> {
+            results.hits.into_iter().map(|h| h.id).collect()
+        };
+
+        let mut last_week = storage
+            .search(&SearchQuery {
+                from: Some(day(8)),
+                to: Some(day(14)),
+                ..SearchQuery::new("paspoort", 10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(last_week.total, 2);
+        last_week.hits.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(ids(last_week), vec!["newest", "recent"]);
+
+        let until = storage
+            .search(&SearchQuery {
+                to: Some(day(5)),
+                ..SearchQuery::new("paspoort", 10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(ids(until), vec!["old"]);
+
+        let recent_first = storage
+            .search(&SearchQuery {
+                sort: SearchSort::Recent,
+                ..SearchQuery::new("paspoort", 10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(recent_first.hits[0].timestamp, Some(day(14).to_rfc3339()));
+        assert_eq!(ids(recent_first), vec!["newest", "recent", "old"]);
+
+        let page = storage
+            .search(&SearchQuery {
+                sort: SearchSort::Recent,
+                offset: 1,
+                ..SearchQuery::new("paspoort", 1)
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.next_offset, Some(2));
+        assert_eq!(ids(page), vec!["recent"]);
+    }
+
+    #[tokio::test]
+    async fn test_resources_are_timestamped_with_their_event_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+
+        // An event from last year, stored today
+        let mut create = commit_event(
+            "evt-1",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        create.time = Some("2024-01-03T09:00:00Z".to_string());
+        storage.store_event(&create).await.unwrap();
+        storage.flush_index().await.unwrap();
+
+        let january = || SearchQuery {
+            doc_type: Some("issue".to_string()),
+            from: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+            to: Some("2024-01-31T23:59:59Z".parse().unwrap()),
+            ..SearchQuery::new("paspoort", 10)
+        };
+        let hits = storage.search(&january()).await.unwrap().hits;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].timestamp.as_deref(),
+            Some("2024-01-03T09:00:00+00:00")
+        );
+
+        // A rebuilt index uses the same time
+        storage.reindex().await.unwrap();
+        assert_eq!(storage.search(&january()).await.unwrap().hits.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_index_recreated_on_schema_change() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            let storage = Storage::new(temp_dir.path()).await.unwrap();
+            storage
+                .store_resource(
+                    "issue-1",
+                    "issue",
+                    &serde_json::json!({"title": "Paspoort"}),
+                )
+                .await
+                .unwrap();
+            let write_txn = storage.db.begin_write().unwrap();
+            write_txn
+                .open_table(META_TABLE)
+                .unwrap()
+                .insert("search_schema_version", "1".as_bytes())
+                .unwrap();
+            write_txn.commit().unwrap();
+            // Commit the queued document, so the writer's threads are idle when the index
+            // directory is recreated
+            storage.flush_index().await.unwrap();
+        }
+
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        let results = storage
+            .search(&SearchQuery::new("title:paspoort", 10))
+            .await
+            .unwrap();
+        assert_eq!(results.hits.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resource_updates_keep_event_documents_with_the_same_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        let issue = "http://localhost:8000/schemas/Issue";
+
+        // The event that creates resource "7" has id "7" as well
+        let create = commit_event(
+            "7",
+            serde_json::json!({
+                "schema": issue,
+                "resource_id": "7",
+                "resource_data": {"title": "Paspoort", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+        let patch = commit_event(
+            "8",
+            serde_json::json!({"schema": issue, "resource_id": "7", "patch": {"status": "closed"}}),
+        );
+        storage.store_event(&patch).await.unwrap();
+        storage.flush_index().await.unwrap();
+
+        let types_of_7 = |results: Vec<SearchResult>| -> Vec<String> {
+            let mut types: Vec<String> = results
+                .into_iter()
+                .filter(|hit| hit.id == "7")
+                .map(|hit| hit.doc_type)
+                .collect();
+            types.sort();
+            types
+        };
+        let results = storage
+            .search(&SearchQuery::new("Paspoort", 10))
+            .await
+            .unwrap()
+            .hits;
+        assert_eq!(types_of_7(results), ["issue", "json.commit"]);
+
+        // Deleting the resource leaves the event document alone
+        let delete = commit_event(
+            "9",
+            serde_json::json!({"schema": issue, "resource_id": "7", "deleted": true}),
+        );
+        storage.store_event(&delete).await.unwrap();
+        let results = storage
+            .search(&SearchQuery::new("Paspoort", 10))
+            .await
+            .unwrap()
+            .hits;
+        assert_eq!(types_of_7(results), ["json.commit"]);
+    }
+
+    #[tokio::test]
+    async fn test_index_commits_on_threshold_and_flush() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = IndexConfig {
+            commit_interval: std::time::Duration::from_secs(3600),
+            commit_threshold: 2,
+            ..IndexConfig::default()
+        };
+        let storage = Storage::with_config(temp_dir.path(), config).await.unwrap();
+        let found = |results: SearchResults| results.hits.len();
+        let mut commits = storage.index_commits();
+
+        storage
+            .store_resource(
+                "issue-1",
+                "issue",
+                &serde_json::json!({"title": "Paspoort"}),
+            )
+            .await
+            .unwrap();
+        // The indexer has not reached the threshold yet
+        let query = SearchQuery::new("paspoort", 10);
+        assert_eq!(found(storage.search(&query).await.unwrap()), 0);
+
+        storage
+            .store_resource(
+                "issue-2",
+                "issue",
+                &serde_json::json!({"title": "Paspoort"}),
+            )
+            .await
+            .unwrap();
+        // Reaching the threshold makes both searchable in a single commit
+        commits.changed().await.unwrap();
+        assert_eq!(*commits.borrow_and_update(), 1);
+        assert_eq!(found(storage.search(&query).await.unwrap()), 2);
+
+        storage
+            .store_resource(
+                "issue-3",
+                "issue",
+                &serde_json::json!({"title": "Paspoort"}),
+            )
+            .await
+            .unwrap();
+        storage.flush_index().await.unwrap();
+        assert_eq!(found(storage.search(&query).await.unwrap()), 3);
+    }
+
+    #[tokio::test]
+    async fn test_close_commits_queued_index_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = IndexConfig {
+            commit_interval: std::time::Duration::from_secs(3600),
+            commit_threshold: 1000,
+            ..IndexConfig::default()
+        };
+        let storage = Storage::with_config(temp_dir.path(), config).await.unwrap();
+        storage
+            .store_resource(
+                "issue-1",
+                "issue",
+                &serde_json::json!({"title": "Paspoort"}),
+            )
+            .await
+            .unwrap();
+        storage.close().await.unwrap();
+
+        // Reopening must not trigger a rebuild: the queued document was committed on close
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        let results = storage
+            .search(&SearchQuery::new("paspoort", 10))
+            .await
+            .unwrap();
+        assert_eq!(results.hits.len(), 1);
+        assert!(storage.check_index().await.unwrap().is_consistent());
+    }
+
     #[tokio::test]
     async fn test_list_resources() {
         let temp_dir = TempDir::new().unwrap();
@@ -772,4 +3414,37 @@ mod tests {
         let retrieved = storage.get_resource("issue-1").await.unwrap();
         assert!(retrieved.is_none());
     }
+
+    #[tokio::test]
+    async fn test_deleted_resource_stays_deleted_after_rebuild() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(temp_dir.path()).await.unwrap();
+        let create = commit_event(
+            "create",
+            serde_json::json!({
+                "schema": "http://localhost:8000/schemas/Issue",
+                "resource_id": "issue-1",
+                "resource_data": {"title": "Paspoort aanvragen", "status": "open"}
+            }),
+        );
+        storage.store_event(&create).await.unwrap();
+
+        let (deletion, _) = storage.delete_resource("issue-1").await.unwrap();
+        assert_eq!(deletion.source, DELETE_SOURCE);
+        assert_eq!(deletion.subject.as_deref(), Some("issue-1"));
+        let commit = deletion.data.as_ref().unwrap();
+        assert_eq!(commit["schema"], "http://localhost:8000/schemas/Issue");
+        assert_eq!(commit["deleted"], true);
+
+        // The deletion is in the event log, so replaying the log does not bring it back
+        let events = storage.list_events_after(None, 10).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].sequence, deletion.sequence);
+        storage.rebuild_projection().await.unwrap();
+        assert!(storage.get_resource("issue-1").await.unwrap().is_none());
+        assert!(matches!(
+            storage.delete_resource("issue-1").await,
+            Err(StorageError::NotFound(_))
+        ));
+    }
 }
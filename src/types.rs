@@ -6,12 +6,46 @@ pub struct PushSubscription {
     /// The endpoint URL for the push service.
     pub endpoint: String,
 
-    /// Expiration time as an ISO string (if provided by the client).
-    #[serde(rename = "expirationTime")]
-    pub expiration_time: Option<String>,
+    /// When the subscription expires, in milliseconds since the epoch, as browsers report it
+    /// (`PushSubscription.expirationTime`); usually `null`.
+    #[serde(rename = "expirationTime", default)]
+    pub expiration_time: Option<f64>,
 
     /// Encryption keys required to send the push message.
     pub keys: PushKeys,
+
+    /// Which events this subscription is notified about (all events when absent).
+    #[serde(default)]
+    pub filters: PushFilters,
+}
+
+/// Topics a push subscription follows.
+///
+/// `subjects`, `assignee` and `mentioned` select zaken: an event matches when it matches any
+/// of those that are set, or always when none is set. `event_types` further restricts the
+/// kinds of notifications.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PushFilters {
+    /// Zaken to follow: issue ids, matched against the event's `subject`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subjects: Vec<String>,
+    /// Notification kinds to be notified about (e.g. `comment.created`, see
+    /// [`crate::push::describe_commit`]); empty for all kinds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<String>,
+    /// Follow the zaken assigned to this email address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    /// Follow comments that mention this email address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mentioned: Option<String>,
+}
+
+impl PushFilters {
+    /// Whether no filter is set, so every event matches.
+    pub fn is_empty(&self) -> bool {
+        self == &PushFilters::default()
+    }
 }
 
 /// Keys associated with a `PushSubscription`.
@@ -20,3 +54,21 @@ pub struct PushKeys {
     pub p256dh: String,
     pub auth: String,
 }
+
+/// How notifications to one push subscription fared.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PushDeliveryStats {
+    /// Notifications the push service accepted
+    pub delivered: u64,
+    /// Notifications given up on: rejected, or still failing after every retry
+    pub failed: u64,
+    /// Extra attempts made after transient failures (429, 5xx, network errors)
+    pub retries: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_delivered: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_failed: Option<chrono::DateTime<chrono::Utc>>,
+    /// Why the last failed notification failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
@@ -0,0 +1,534 @@
+//! Validation of incoming events.
+//!
+//! CloudEvents are checked against the CloudEvents 1.0 specification and the NL-GOV profile
+//! for CloudEvents before they are accepted ([`validate_cloudevent`]).
+//!
+//! JSONCommit payloads are checked against the JSON Schemas served at `/schemas/{name}`. A
+//! commit names the schema of its resource in `JSONCommit.schema`, usually as a URL such as
+//! `http://localhost:8000/schemas/Issue`. The resource that results from applying the commit
+//! (its `resource_data`, or the existing resource with the `patch` merged in) must match that
+//! schema. Commits naming a schema this server does not publish are not validated.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::Validator;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::schemas;
+
+/// Schemas that describe resources (the item types of the zaaksysteem).
+const RESOURCE_SCHEMAS: [&str; 5] = ["Issue", "Comment", "Task", "Planning", "Document"];
+
+/// Validators for [`RESOURCE_SCHEMAS`], compiled once from [`schemas::get_all_schemas`].
+static VALIDATORS: LazyLock<HashMap<&'static str, Validator>> = LazyLock::new(|| {
+    let all = schemas::get_all_schemas();
+    RESOURCE_SCHEMAS
+        .into_iter()
+        .filter_map(|name| {
+            let schema = all.get(name)?;
+            match jsonschema::validator_for(schema) {
+                Ok(validator) => Some((name, validator)),
+                Err(e) => {
+                    eprintln!("[validation] schema {} does not compile: {}", name, e);
+                    None
+                }
+            }
+        })
+        .collect()
+});
+
+/// One way in which a resource does not match its schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    /// JSON Pointer to the offending value in the resource (e.g. `/status`)
+    pub path: String,
+    pub message: String,
+}
+
+/// Name of the schema a `JSONCommit.schema` value refers to: its last path segment.
+pub fn schema_name(schema: &str) -> &str {
+    schema
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(schema)
+}
+
+/// Validate `resource` against the resource schema named by `schema`.
+///
+/// Returns every violation found; an empty list means the resource is valid or the schema is
+/// not one of the published resource schemas.
+pub fn validate_resource(schema: &str, resource: &Value) -> Vec<Violation> {
+    let Some(validator) = VALIDATORS.get(schema_name(schema)) else {
+        return Vec::new();
+    };
+
+    validator
+        .iter_errors(resource)
+        .map(|error| {
+            let mut path = error.instance_path.to_string();
+            // Point at the missing property itself rather than at the object lacking it
+            if let ValidationErrorKind::Required { property } = &error.kind {
+                if let Some(property) = property.as_str() {
+                    path = format!("{}/{}", path, property);
+                }
+            }
+            Violation {
+                path,
+                message: error.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Context attributes defined by CloudEvents 1.0 itself; every other member of a JSON-format
+/// event is an extension attribute.
+const CONTEXT_ATTRIBUTES: [&str; 10] = [
+    "specversion",
+    "id",
+    "source",
+    "type",
+    "subject",
+    "time",
+    "datacontenttype",
+    "dataschema",
+    "data",
+    "data_base64",
+];
+
+/// Extension attribute names longer than this are discouraged by CloudEvents 1.0.
+const MAX_EXTENSION_NAME_LEN: usize = 20;
+
+/// How a finding affects the event: errors reject it, warnings are only reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Specification a finding comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    /// CloudEvents 1.0 core specification and its JSON event format
+    #[serde(rename = "cloudevents")]
+    CloudEvents,
+    /// NL-GOV profile for CloudEvents
+    NlGov,
+}
+
+/// One way in which a CloudEvent deviates from the specification or the NL-GOV profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    /// Context attribute the finding is about (e.g. `time`); empty for the event as a whole
+    pub attribute: String,
+    pub severity: Severity,
+    pub profile: Profile,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(attribute: &str, message: impl Into<String>) -> Self {
+        Self {
+            attribute: attribute.to_string(),
+            severity: Severity::Error,
+            profile: Profile::CloudEvents,
+            message: message.into(),
+        }
+    }
+
+    fn nl_gov(attribute: &str, message: impl Into<String>) -> Self {
+        Self {
+            attribute: attribute.to_string(),
+            severity: Severity::Warning,
+            profile: Profile::NlGov,
+            message: message.into(),
+        }
+    }
+
+    fn warning(attribute: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Finding::error(attribute, message)
+        }
+    }
+}
+
+/// Validate a CloudEvent in its JSON event format.
+///
+/// Returns every finding, errors and warnings alike; the event is acceptable when none of them
+/// is a [`Severity::Error`]. Attributes with a `null` value are treated as absent.
+pub fn validate_cloudevent(event: &Value) -> Vec<Finding> {
+    let Some(event) = event.as_object() else {
+        return vec![Finding::error("", "a CloudEvent must be a JSON object")];
+    };
+    let mut findings = Vec::new();
+    let attribute = |name: &str| event.get(name).filter(|v| !v.is_null());
+
+    // Required attributes: present, strings and non-empty
+    let mut required = |name: &str| -> Option<&str> {
+        match attribute(name) {
+            None => {
+                findings.push(Finding::error(name, format!("{} is required", name)));
+                None
+            }
+            Some(Value::String(s)) if s.is_empty() => {
+                findings.push(Finding::error(name, format!("{} must not be empty", name)));
+                None
+            }
+            Some(Value::String(s)) => Some(s.as_str()),
+            Some(_) => {
+                findings.push(Finding::error(name, format!("{} must be a string", name)));
+                None
+            }
+        }
+    };
+    let specversion = required("specversion");
+    let id = required("id");
+    let source = required("source");
+    let event_type = required("type");
+
+    // Optional attributes: strings when present
+    let mut optional = |name: &str| -> Option<&str> {
+        match attribute(name)? {
+            Value::String(s) => Some(s.as_str()),
+            _ => {
+                findings.push(Finding::error(name, format!("{} must be a string", name)));
+                None
+            }
+        }
+    };
+    let subject = optional("subject");
+    let time = optional("time");
+    let datacontenttype = optional("datacontenttype");
+    let dataschema = optional("dataschema");
+    let data_base64 = optional("data_base64");
+
+    if let Some(specversion) = specversion {
+        if specversion != "1.0" {
+            findings.push(Finding::error(
+                "specversion",
+                format!(
+                    "unsupported specversion {:?}, expected \"1.0\"",
+                    specversion
+                ),
+            ));
+        }
+    }
+
+    if let Some(id) = id {
+        if uuid::Uuid::parse_str(id).is_err() {
+            findings.push(Finding::nl_gov("id", "id should be a UUID"));
+        }
+    }
+
+    if let Some(source) = source {
+        if !is_uri_reference(source) {
+            findings.push(Finding::error("source", "source must be a URI-reference"));
+        } else if !is_absolute_uri(source) {
+            findings.push(Finding::nl_gov(
+                "source",
+                "source should be an absolute URI, e.g. urn:nld:oin:<oin>:systeem:<name>",
+            ));
+        }
+    }
+
+    if let Some(event_type) = event_type {
+        if !event_type.contains('.') {
+            findings.push(Finding::nl_gov(
+                "type",
+                "type should be prefixed with a reverse-DNS name, e.g. nl.overheid.zaken.zaak-gewijzigd",
+            ));
+        }
+    }
+
+    if subject == Some("") {
+        findings.push(Finding::error("subject", "subject must not be empty"));
+    }
+
+    match time {
+        Some(time) => {
+            if chrono::DateTime::parse_from_rfc3339(time).is_err() {
+                findings.push(Finding::error(
+                    "time",
+                    format!("time {:?} is not an RFC 3339 timestamp", time),
+                ));
+            }
+        }
+        None => findings.push(Finding::nl_gov("time", "time should be set")),
+    }
+
+    if let Some(dataschema) = dataschema {
+        if !is_absolute_uri(dataschema) {
+            findings.push(Finding::error(
+                "dataschema",
+                "dataschema must be an absolute URI",
+            ));
+        }
+    }
+
+    let data = attribute("data");
+    if data.is_some() && data_base64.is_some() {
+        findings.push(Finding::error(
+            "data_base64",
+            "data and data_base64 must not both be present",
+        ));
+    }
+    match datacontenttype {
+        Some(content_type) if !is_media_type(content_type) => {
+            findings.push(Finding::error(
+                "datacontenttype",
+                format!("datacontenttype {:?} is not a media type", content_type),
+            ));
+        }
+        // JSON data (anything but a string) can only be declared as JSON
+        Some(content_type)
+            if data.is_some_and(|d| !d.is_string()) && !is_json_media_type(content_type) =>
+        {
+            findings.push(Finding::error(
+                "datacontenttype",
+                format!(
+                    "data is JSON but datacontenttype is {:?}; use application/json",
+                    content_type
+                ),
+            ));
+        }
+        Some(_) => {}
+        None if data.is_some() || data_base64.is_some() => findings.push(Finding::nl_gov(
+            "datacontenttype",
+            "datacontenttype should be set when the event carries data",
+        )),
+        None => {}
+    }
+
+    for (name, value) in event {
+        if CONTEXT_ATTRIBUTES.contains(&name.as_str()) || value.is_null() {
+            continue;
+        }
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        {
+            findings.push(Finding::error(
+                name,
+                "extension attribute names must consist of lower-case letters and digits",
+            ));
+        } else if name.len() > MAX_EXTENSION_NAME_LEN {
+            findings.push(Finding::warning(
+                name,
+                format!(
+                    "extension attribute names should not exceed {} characters",
+                    MAX_EXTENSION_NAME_LEN
+                ),
+            ));
+        }
+        let valid_value = match value {
+            Value::String(_) | Value::Bool(_) => true,
+            Value::Number(n) => n.is_i64(),
+            _ => false,
+        };
+        if !valid_value {
+            findings.push(Finding::error(
+                name,
+                "extension attributes must be a string, boolean or integer",
+            ));
+        }
+    }
+    if let Some(dataref) = attribute("dataref").and_then(Value::as_str) {
+        if !is_uri_reference(dataref) {
+            findings.push(Finding::error("dataref", "dataref must be a URI-reference"));
+        }
+    }
+
+    findings
+}
+
+/// Whether any finding rejects the event.
+pub fn has_errors(findings: &[Finding]) -> bool {
+    findings.iter().any(|f| f.severity == Severity::Error)
+}
+
+/// RFC 3986 URI-reference: only URI characters, well-formed percent-encodings and, when a
+/// scheme is present, a valid one.
+fn is_uri_reference(value: &str) -> bool {
+    const ALLOWED: &str = "-._~:/?#[]@!$&'()*+,;=";
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3);
+                if !hex.is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit)) {
+                    return false;
+                }
+                i += 3;
+                continue;
+            }
+            b if b.is_ascii_alphanumeric() || ALLOWED.as_bytes().contains(&b) => {}
+            _ => return false,
+        }
+        i += 1;
+    }
+    // A colon before any '/', '?' or '#' must end a valid scheme
+    let head = value.split(['/', '?', '#']).next().unwrap_or("");
+    match head.split_once(':') {
+        Some((scheme, _)) => is_scheme(scheme),
+        None => true,
+    }
+}
+
+/// RFC 3986 absolute URI: a URI-reference with a scheme.
+fn is_absolute_uri(value: &str) -> bool {
+    is_uri_reference(value)
+        && value
+            .split_once(':')
+            .is_some_and(|(scheme, _)| is_scheme(scheme))
+}
+
+fn is_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// RFC 2046 media type: `type/subtype`, optionally followed by `;` parameters.
+fn is_media_type(value: &str) -> bool {
+    let is_token = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+    };
+    let essence = value.split(';').next().unwrap_or("").trim();
+    essence
+        .split_once('/')
+        .is_some_and(|(kind, subtype)| is_token(kind) && is_token(subtype))
+}
+
+/// Whether a media type declares JSON: `application/json` or a `+json` structured suffix.
+fn is_json_media_type(value: &str) -> bool {
+    let essence = value
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json" || essence == "text/json" || essence.ends_with("+json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_name() {
+        assert_eq!(schema_name("http://localhost:8000/schemas/Issue"), "Issue");
+        assert_eq!(schema_name("Comment"), "Comment");
+        assert_eq!(schema_name("/schemas/Task/"), "Task");
+    }
+
+    #[test]
+    fn test_validate_resource_reports_violations() {
+        let issue = json!({"title": "Paspoort aanvragen", "status": "open"});
+        assert!(validate_resource("http://localhost:8000/schemas/Issue", &issue).is_empty());
+
+        let violations = validate_resource("Issue", &json!({"status": "banana"}));
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(violations.len(), 2, "{:?}", violations);
+        assert!(paths.contains(&"/status"));
+        assert!(paths.contains(&"/title"));
+
+        // Schemas this server does not publish are not enforced
+        assert!(validate_resource("LLMAnalysis", &json!({"anything": 1})).is_empty());
+    }
+
+    #[test]
+    fn test_conforming_cloudevent_has_no_findings() {
+        let event = json!({
+            "specversion": "1.0",
+            "id": "01890a5d-ac96-774b-bcce-b302099a8057",
+            "source": "urn:nld:oin:00000001823288444000:systeem:zaaksysteem",
+            "type": "nl.gemeente.zaken.json-commit",
+            "time": "2024-01-15T10:30:00Z",
+            "datacontenttype": "application/json",
+            "dataschema": "http://localhost:8000/schemas/JSONCommit",
+            "sequence": "00000000000000000001",
+            "data": {"resource_id": "1"}
+        });
+        assert_eq!(validate_cloudevent(&event), Vec::new());
+    }
+
+    #[test]
+    fn test_cloudevent_findings() {
+        let event = json!({
+            "specversion": "0.3",
+            "id": "",
+            "source": "bad source",
+            "type": "json.commit",
+            "time": "15-01-2024 10:30",
+            "datacontenttype": "text/plain",
+            "data": {"resource_id": "1"},
+            "Actor": "alice",
+            "traceinfo": {"span": 1}
+        });
+        let findings = validate_cloudevent(&event);
+        let errors: Vec<&str> = findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .map(|f| f.attribute.as_str())
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "id",
+                "specversion",
+                "source",
+                "time",
+                "datacontenttype",
+                "Actor",
+                "traceinfo"
+            ],
+            "{:?}",
+            findings
+        );
+        assert!(has_errors(&findings));
+
+        // A bare CloudEvents 1.0 event is valid, with NL-GOV recommendations as warnings
+        let event =
+            json!({"specversion": "1.0", "id": "1", "source": "zaaksysteem", "type": "created"});
+        let findings = validate_cloudevent(&event);
+        assert!(!has_errors(&findings), "{:?}", findings);
+        assert!(findings.iter().all(|f| f.profile == Profile::NlGov));
+        let attributes: Vec<&str> = findings.iter().map(|f| f.attribute.as_str()).collect();
+        assert_eq!(attributes, ["id", "source", "type", "time"]);
+
+        let findings = validate_cloudevent(&json!({"id": "1"}));
+        assert_eq!(
+            findings
+                .iter()
+                .filter(|f| f.severity == Severity::Error)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_uri_reference() {
+        assert!(is_uri_reference("zaaksysteem"));
+        assert!(is_uri_reference("/sensors/tn-1234567/alerts"));
+        assert!(is_uri_reference(
+            "urn:nld:oin:00000001823288444000:systeem:BRP"
+        ));
+        assert!(is_uri_reference("https://example.com/path?q=a%20b#frag"));
+        assert!(!is_uri_reference("has space"));
+        assert!(!is_uri_reference("1http://example.com"));
+        assert!(!is_uri_reference("bad%zz"));
+        assert!(is_absolute_uri("https://example.com"));
+        assert!(!is_absolute_uri("./relative"));
+    }
+}
@@ -0,0 +1,288 @@
+//! VAPID (RFC 8292) keys that identify this server to push services.
+//!
+//! Browsers subscribe with the public key (served at `GET /api/push/vapid-public-key`) and
+//! push services only accept messages signed with the matching private key. The key pair is
+//! configured with, in order of precedence:
+//!
+//! - `VAPID_PRIVATE_KEY`: the raw private key, base64url encoded (as generated by the
+//!   `generate_vapid_keys` binary or the `web-push` CLI)
+//! - `VAPID_PRIVATE_KEY_FILE`: a file holding that key, or a PKCS#8 PEM encoded EC private key
+//! - otherwise `DATA_DIR/vapid_private_key`, generated on first start
+//!
+//! `VAPID_SUBJECT` is the contact (`mailto:` or `https:` URL) sent as the `sub` claim.
+
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jwt_simple::algorithms::ES256KeyPair;
+use web_push::{
+    PartialVapidSignatureBuilder, SubscriptionInfo, VapidSignature, VapidSignatureBuilder,
+    WebPushError,
+};
+
+/// File in the data directory holding the generated private key.
+const PRIVATE_KEY_FILE: &str = "vapid_private_key";
+
+/// `sub` claim used when `VAPID_SUBJECT` is not set.
+pub const DEFAULT_SUBJECT: &str = "mailto:admin@localhost";
+
+/// Failure to load or create the VAPID keys.
+#[derive(Debug, thiserror::Error)]
+pub enum VapidError {
+    #[error("invalid VAPID private key: {0}")]
+    InvalidKey(String),
+    #[error("cannot read or write VAPID key file {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// Where the VAPID keys come from; see the module docs for the precedence.
+#[derive(Debug, Clone, Default)]
+pub struct VapidConfig {
+    /// Raw private key, base64url encoded
+    pub private_key: Option<String>,
+    /// File holding the private key
+    pub private_key_file: Option<PathBuf>,
+    /// Contact for push services (`sub` claim)
+    pub subject: Option<String>,
+}
+
+impl VapidConfig {
+    /// Configuration from `VAPID_PRIVATE_KEY`, `VAPID_PRIVATE_KEY_FILE` and `VAPID_SUBJECT`.
+    pub fn from_env() -> Self {
+        Self {
+            private_key: std::env::var("VAPID_PRIVATE_KEY").ok(),
+            private_key_file: std::env::var_os("VAPID_PRIVATE_KEY_FILE").map(PathBuf::from),
+            subject: std::env::var("VAPID_SUBJECT").ok(),
+        }
+    }
+}
+
+/// A VAPID key pair and the contact sent with every signature.
+#[derive(Clone)]
+pub struct VapidKeys {
+    signer: PartialVapidSignatureBuilder,
+    private_key: String,
+    public_key: String,
+    subject: String,
+}
+
+impl VapidKeys {
+    /// Keys from a raw, base64url encoded private key.
+    pub fn from_base64(private_key: &str, subject: impl Into<String>) -> Result<Self, VapidError> {
+        let private_key = private_key.trim();
+        let bytes = URL_SAFE_NO_PAD
+            .decode(private_key.trim_end_matches('='))
+            .map_err(|e| VapidError::InvalidKey(e.to_string()))?;
+        let key_pair =
+            ES256KeyPair::from_bytes(&bytes).map_err(|e| VapidError::InvalidKey(e.to_string()))?;
+        Self::from_key_pair(key_pair, subject.into())
+    }
+
+    /// Keys from a file holding a base64url private key or a PKCS#8 PEM encoded EC private key.
+    pub fn from_file(path: &Path, subject: impl Into<String>) -> Result<Self, VapidError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| VapidError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        if !contents.contains("-----BEGIN") {
+            return Self::from_base64(&contents, subject);
+        }
+        let key_pair = ES256KeyPair::from_pem(&contents).map_err(|e| {
+            VapidError::InvalidKey(format!(
+                "{} (PEM keys must be PKCS#8: `openssl pkcs8 -topk8 -nocrypt`)",
+                e
+            ))
+        })?;
+        Self::from_key_pair(key_pair, subject.into())
+    }
+
+    /// A new random key pair.
+    pub fn generate(subject: impl Into<String>) -> Self {
+        Self::from_key_pair(ES256KeyPair::generate(), subject.into())
+            .expect("a generated key pair is valid")
+    }
+
+    /// Load the keys as `config` says, generating and storing a key pair in `data_dir` when
+    /// it names none.
+    pub fn load(config: &VapidConfig, data_dir: &Path) -> Result<Self, VapidError> {
+        let subject = config.subject.clone().unwrap_or_else(|| {
+            eprintln!(
+                "[push] VAPID_SUBJECT is not set; using {} as contact for push services",
+                DEFAULT_SUBJECT
+            );
+            DEFAULT_SUBJECT.to_string()
+        });
+
+        if let Some(private_key) = &config.private_key {
+            return Self::from_base64(private_key, subject);
+        }
+        if let Some(path) = &config.private_key_file {
+            return Self::from_file(path, subject);
+        }
+
+        let path = data_dir.join(PRIVATE_KEY_FILE);
+        if path.exists() {
+            return Self::from_file(&path, subject);
+        }
+        let keys = Self::generate(subject);
+        keys.write_private_key(&path)?;
+        println!("[push] generated VAPID key pair in {}", path.display());
+        Ok(keys)
+    }
+
+    /// Write the private key (base64url) to a new file at `path`, readable only by the owner.
+    /// The file is created with those permissions, so the key is never readable by others,
+    /// and an existing file is not overwritten.
+    pub fn write_private_key(&self, path: &Path) -> Result<(), VapidError> {
+        use std::io::Write;
+
+        let io_error = |source| VapidError::Io {
+            path: path.display().to_string(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path).map_err(io_error)?;
+        writeln!(file, "{}", self.private_key).map_err(io_error)?;
+        Ok(())
+    }
+
+    /// Public key (uncompressed point, base64url) for `PushManager.subscribe`'s
+    /// `applicationServerKey`.
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Private key, base64url encoded.
+    pub fn private_key(&self) -> &str {
+        &self.private_key
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Signature for a message to `subscription`, with the `sub` claim set.
+    pub fn sign(&self, subscription: &SubscriptionInfo) -> Result<VapidSignature, WebPushError> {
+        let mut builder = self.signer.clone().add_sub_info(subscription);
+        builder.add_claim("sub", self.subject.as_str());
+        builder.build()
+    }
+
+    fn from_key_pair(key_pair: ES256KeyPair, subject: String) -> Result<Self, VapidError> {
+        let private_key = URL_SAFE_NO_PAD.encode(key_pair.to_bytes());
+        let signer =
+            VapidSignatureBuilder::from_base64_no_sub(&private_key, web_push::URL_SAFE_NO_PAD)
+                .map_err(|e| VapidError::InvalidKey(e.to_string()))?;
+        Ok(Self {
+            public_key: URL_SAFE_NO_PAD.encode(signer.get_public_key()),
+            signer,
+            private_key,
+            subject,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_round_trip_and_sign_with_subject() {
+        let keys = VapidKeys::generate("mailto:beheer@gemeente.nl");
+        let loaded =
+            VapidKeys::from_base64(keys.private_key(), "mailto:beheer@gemeente.nl").unwrap();
+        assert_eq!(loaded.public_key(), keys.public_key());
+        // An uncompressed P-256 point: 65 bytes
+        assert_eq!(URL_SAFE_NO_PAD.decode(keys.public_key()).unwrap().len(), 65);
+
+        let subscription = SubscriptionInfo::new(
+            "https://push.example.com/send/abc",
+            "BNcRdreALRFXTkOOUHK1EtK2wtaz5Ry4YfYCA_0QTpQtUbVlUls0VJXg7A8u-Ts1XbjhazAkj7I99e8QcYP7DkM",
+            "tBHItJI5svbpez7KI4CCXg",
+        );
+        let signature = keys.sign(&subscription).unwrap();
+        let claims = signature.auth_t.split('.').nth(1).unwrap();
+        let claims: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap();
+        assert_eq!(claims["sub"], "mailto:beheer@gemeente.nl");
+        assert_eq!(claims["aud"], "https://push.example.com");
+
+        assert!(matches!(
+            VapidKeys::from_base64("not a key", DEFAULT_SUBJECT),
+            Err(VapidError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_generated_key_is_kept_in_data_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = VapidConfig::default();
+        let first = VapidKeys::load(&config, temp_dir.path()).unwrap();
+        let second = VapidKeys::load(&config, temp_dir.path()).unwrap();
+        assert_eq!(first.public_key(), second.public_key());
+        assert_eq!(first.subject(), DEFAULT_SUBJECT);
+
+        let path = temp_dir.path().join(PRIVATE_KEY_FILE);
+        let from_file = VapidKeys::from_file(&path, DEFAULT_SUBJECT).unwrap();
+        assert_eq!(from_file.public_key(), first.public_key());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // An existing key file is never overwritten
+        assert!(matches!(
+            VapidKeys::generate(DEFAULT_SUBJECT).write_private_key(&path),
+            Err(VapidError::Io { .. })
+        ));
+        assert_eq!(
+            VapidKeys::from_file(&path, DEFAULT_SUBJECT)
+                .unwrap()
+                .public_key(),
+            first.public_key()
+        );
+    }
+
+    #[test]
+    fn test_configured_key_takes_precedence() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let key = VapidKeys::generate(DEFAULT_SUBJECT);
+        let file = VapidKeys::generate(DEFAULT_SUBJECT);
+        let key_file = temp_dir.path().join("vapid.key");
+        file.write_private_key(&key_file).unwrap();
+
+        let config = VapidConfig {
+            private_key: Some(key.private_key().to_string()),
+            private_key_file: Some(key_file.clone()),
+            subject: Some("mailto:beheer@gemeente.nl".to_string()),
+        };
+        let loaded = VapidKeys::load(&config, temp_dir.path()).unwrap();
+        assert_eq!(loaded.public_key(), key.public_key());
+        assert_eq!(loaded.subject(), "mailto:beheer@gemeente.nl");
+
+        let config = VapidConfig {
+            private_key: None,
+            ..config
+        };
+        let loaded = VapidKeys::load(&config, temp_dir.path()).unwrap();
+        assert_eq!(loaded.public_key(), file.public_key());
+        // Nothing is generated when a key is configured
+        assert!(!temp_dir.path().join(PRIVATE_KEY_FILE).exists());
+    }
+}